@@ -7,6 +7,9 @@ use std::fs::{self, File};
 use std::io::Write;
 use std::path::{Path, PathBuf};
 use tar::operations;
+use tar::operations::create::CreateOptions;
+use tar::operations::extract::ExtractOptions;
+use tar::operations::list::ListOptions;
 use tempfile::TempDir;
 
 fn main() {
@@ -39,7 +42,7 @@ fn collect_files(dir: &Path) -> Vec<PathBuf> {
 fn build_archive(archive_path: &Path, source_dir: &Path) {
     let files = collect_files(source_dir);
     let refs: Vec<&Path> = files.iter().map(|p| p.as_path()).collect();
-    operations::create::create_archive(archive_path, &refs, false, false).unwrap();
+    operations::create::create_archive(archive_path, &refs, &CreateOptions::default()).unwrap();
 }
 
 // ---------------------------------------------------------------------------
@@ -57,7 +60,8 @@ fn create_archive_10_files(bencher: divan::Bencher) {
 
     bencher.bench_local(|| {
         let refs: Vec<&Path> = files.iter().map(|p| p.as_path()).collect();
-        operations::create::create_archive(&archive_path, &refs, false, false).unwrap();
+        operations::create::create_archive(&archive_path, &refs, &CreateOptions::default())
+            .unwrap();
     });
 }
 
@@ -72,7 +76,8 @@ fn create_archive_100_files(bencher: divan::Bencher) {
 
     bencher.bench_local(|| {
         let refs: Vec<&Path> = files.iter().map(|p| p.as_path()).collect();
-        operations::create::create_archive(&archive_path, &refs, false, false).unwrap();
+        operations::create::create_archive(&archive_path, &refs, &CreateOptions::default())
+            .unwrap();
     });
 }
 
@@ -87,7 +92,12 @@ fn create_archive_directory(bencher: divan::Bencher) {
     let archive_path = out.path().join("bench.tar");
 
     bencher.bench_local(|| {
-        operations::create::create_archive(&archive_path, &[sub.as_path()], false, false).unwrap();
+        operations::create::create_archive(
+            &archive_path,
+            &[sub.as_path()],
+            &CreateOptions::default(),
+        )
+        .unwrap();
     });
 }
 
@@ -104,7 +114,7 @@ fn list_archive_50_files(bencher: divan::Bencher) {
     build_archive(&archive_path, source.path());
 
     bencher.bench_local(|| {
-        operations::list::list_archive(&archive_path, false).unwrap();
+        operations::list::list_archive(&archive_path, &ListOptions::default()).unwrap();
     });
 }
 
@@ -117,7 +127,11 @@ fn list_archive_verbose_50_files(bencher: divan::Bencher) {
     build_archive(&archive_path, source.path());
 
     bencher.bench_local(|| {
-        operations::list::list_archive(&archive_path, true).unwrap();
+        let options = ListOptions {
+            verbose: 1,
+            ..ListOptions::default()
+        };
+        operations::list::list_archive(&archive_path, &options).unwrap();
     });
 }
 
@@ -138,7 +152,8 @@ fn extract_archive_20_files(bencher: divan::Bencher) {
         .with_inputs(|| TempDir::new().unwrap())
         .bench_local_values(|extract_dir| {
             std::env::set_current_dir(extract_dir.path()).unwrap();
-            operations::extract::extract_archive(&archive_path, false).unwrap();
+            operations::extract::extract_archive(&archive_path, &ExtractOptions::default())
+                .unwrap();
         });
     std::env::set_current_dir(original_dir).unwrap();
 }
@@ -0,0 +1,264 @@
+// This file is part of the uutils tar package.
+//
+// For the full copyright and license information, please view the LICENSE
+// file that was distributed with this source code.
+
+//! POSIX access control list support (`--acls`).
+//!
+//! ACLs are stored in PAX extended headers as `SCHILY.acl.access` and
+//! `SCHILY.acl.default` records holding the short text form
+//! (`user::rw-,user:alice:r--,group::r--,mask::r--,other::---`), as GNU tar
+//! and star do. On Linux they are read and written through the
+//! `system.posix_acl_access` and `system.posix_acl_default` extended
+//! attributes, so no ACL library is needed.
+
+use crate::member::Member;
+use std::io;
+use std::path::Path;
+
+/// PAX keyword holding the access ACL of a member
+pub const ACL_ACCESS_PAX_KEY: &str = "SCHILY.acl.access";
+/// PAX keyword holding the default ACL of a directory member
+pub const ACL_DEFAULT_PAX_KEY: &str = "SCHILY.acl.default";
+
+/// Extended attributes through which Linux exposes ACLs. They are handled
+/// here rather than by `--xattrs`.
+pub const ACL_XATTRS: [&str; 2] = ["system.posix_acl_access", "system.posix_acl_default"];
+
+/// ACLs recorded for an archive member, in text form
+#[derive(Debug, Clone, Default)]
+pub struct ArchivedAcls {
+    pub access: Option<String>,
+    pub default: Option<String>,
+}
+
+impl ArchivedAcls {
+    /// Returns whether the member carries no ACL at all.
+    pub fn is_empty(&self) -> bool {
+        self.access.is_none() && self.default.is_none()
+    }
+}
+
+/// Reads the ACLs of `path` as PAX records.
+///
+/// Only extended ACLs are stored; a file whose ACL is fully described by
+/// its mode bits yields no records, as does a filesystem without ACLs.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn read_pax_records(path: &Path) -> io::Result<Vec<(String, Vec<u8>)>> {
+    let mut records = Vec::new();
+    for (key, attr) in [ACL_ACCESS_PAX_KEY, ACL_DEFAULT_PAX_KEY]
+        .into_iter()
+        .zip(ACL_XATTRS)
+    {
+        let value = match xattr::get_deref(path, attr) {
+            Ok(Some(value)) => value,
+            Ok(None) => continue,
+            Err(e) if e.kind() == io::ErrorKind::Unsupported => continue,
+            Err(e) => return Err(e),
+        };
+        let text = linux::to_text(&value).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: Invalid ACL in {attr}", path.display()),
+            )
+        })?;
+        records.push((key.to_string(), text.into_bytes()));
+    }
+    Ok(records)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub fn read_pax_records(_path: &Path) -> io::Result<Vec<(String, Vec<u8>)>> {
+    Ok(Vec::new())
+}
+
+/// Collects the ACL records stored for `member`.
+pub fn archived_acls(member: &Member) -> ArchivedAcls {
+    let text = |keyword| {
+        member
+            .record(keyword)
+            .and_then(|value| String::from_utf8(value.to_vec()).ok())
+    };
+    ArchivedAcls {
+        access: text(ACL_ACCESS_PAX_KEY),
+        default: text(ACL_DEFAULT_PAX_KEY),
+    }
+}
+
+/// Applies `acls` to the extracted member at `path`.
+///
+/// Failures are reported as warnings, like failures to restore extended
+/// attributes.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn restore_acls(path: &Path, acls: &ArchivedAcls) {
+    let pairs = [
+        (&acls.access, ACL_XATTRS[0]),
+        (&acls.default, ACL_XATTRS[1]),
+    ];
+    for (text, attr) in pairs {
+        let Some(text) = text else {
+            continue;
+        };
+        let result = linux::from_text(text)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            .and_then(|value| xattr::set(path, attr, &value));
+        if let Err(e) = result {
+            uucore::show_warning!("{}: Cannot set ACL: {e}", path.display());
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub fn restore_acls(_path: &Path, _acls: &ArchivedAcls) {}
+
+/// Conversion between the kernel's binary ACL representation and text.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod linux {
+    use uucore::entries::{gid2grp, grp2gid, uid2usr, usr2uid};
+
+    const VERSION: u32 = 2;
+    const UNDEFINED_ID: u32 = u32::MAX;
+
+    const USER_OBJ: u16 = 0x01;
+    const USER: u16 = 0x02;
+    const GROUP_OBJ: u16 = 0x04;
+    const GROUP: u16 = 0x08;
+    const MASK: u16 = 0x10;
+    const OTHER: u16 = 0x20;
+
+    /// Renders a `system.posix_acl_*` value as comma-separated text.
+    ///
+    /// Named entries use the user or group name when it is known locally
+    /// and the numeric id otherwise.
+    pub(super) fn to_text(value: &[u8]) -> Option<String> {
+        let (header, body) = value.split_first_chunk::<4>()?;
+        if u32::from_le_bytes(*header) != VERSION || body.len() % 8 != 0 {
+            return None;
+        }
+
+        let mut entries = Vec::new();
+        for chunk in body.chunks_exact(8) {
+            let tag = u16::from_le_bytes([chunk[0], chunk[1]]);
+            let perm = u16::from_le_bytes([chunk[2], chunk[3]]);
+            let id = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+
+            let (kind, qualifier) = match tag {
+                USER_OBJ => ("user", String::new()),
+                USER => ("user", uid2usr(id).unwrap_or_else(|_| id.to_string())),
+                GROUP_OBJ => ("group", String::new()),
+                GROUP => ("group", gid2grp(id).unwrap_or_else(|_| id.to_string())),
+                MASK => ("mask", String::new()),
+                OTHER => ("other", String::new()),
+                _ => return None,
+            };
+            entries.push(format!("{kind}:{qualifier}:{}", perm_text(perm)));
+        }
+        Some(entries.join(","))
+    }
+
+    /// Parses the text form of an ACL into a `system.posix_acl_*` value.
+    ///
+    /// Entries may be separated by commas or newlines and may carry
+    /// `#` comments. Star's trailing `:id` field is used when the name is
+    /// unknown on this system.
+    pub(super) fn from_text(text: &str) -> Result<Vec<u8>, String> {
+        let mut entries = Vec::new();
+        for entry in text.split([',', '\n']) {
+            let entry = entry.split('#').next().unwrap_or_default().trim();
+            if entry.is_empty() {
+                continue;
+            }
+            entries.push(parse_entry(entry).ok_or_else(|| format!("invalid ACL entry '{entry}'"))?);
+        }
+        // The kernel requires entries ordered by tag, then by id.
+        entries.sort_unstable_by_key(|&(tag, _, id)| (tag, id));
+
+        let mut value = VERSION.to_le_bytes().to_vec();
+        for (tag, perm, id) in entries {
+            value.extend_from_slice(&tag.to_le_bytes());
+            value.extend_from_slice(&perm.to_le_bytes());
+            value.extend_from_slice(&id.to_le_bytes());
+        }
+        Ok(value)
+    }
+
+    /// Parses one `kind:qualifier:perms[:id]` entry into (tag, perm, id).
+    fn parse_entry(entry: &str) -> Option<(u16, u16, u32)> {
+        let mut fields = entry.split(':');
+        let kind = fields.next()?;
+        let qualifier = fields.next()?;
+        let perm = parse_perm(fields.next()?)?;
+        let fallback_id = fields.next().map(str::parse::<u32>).transpose().ok()?;
+        if fields.next().is_some() {
+            return None;
+        }
+
+        let resolve = |lookup: fn(&str) -> std::io::Result<u32>| {
+            lookup(qualifier)
+                .ok()
+                .or_else(|| qualifier.parse().ok())
+                .or(fallback_id)
+        };
+
+        match (kind, qualifier.is_empty()) {
+            ("user" | "u", true) => Some((USER_OBJ, perm, UNDEFINED_ID)),
+            ("user" | "u", false) => Some((USER, perm, resolve(usr2uid)?)),
+            ("group" | "g", true) => Some((GROUP_OBJ, perm, UNDEFINED_ID)),
+            ("group" | "g", false) => Some((GROUP, perm, resolve(grp2gid)?)),
+            ("mask" | "m", true) => Some((MASK, perm, UNDEFINED_ID)),
+            ("other" | "o", true) => Some((OTHER, perm, UNDEFINED_ID)),
+            _ => None,
+        }
+    }
+
+    fn perm_text(perm: u16) -> String {
+        [(4, 'r'), (2, 'w'), (1, 'x')]
+            .iter()
+            .map(|&(bit, c)| if perm & bit != 0 { c } else { '-' })
+            .collect()
+    }
+
+    fn parse_perm(text: &str) -> Option<u16> {
+        text.chars().try_fold(0, |perm, c| match c {
+            'r' => Some(perm | 4),
+            'w' => Some(perm | 2),
+            'x' => Some(perm | 1),
+            '-' => Some(perm),
+            _ => None,
+        })
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_text_roundtrip() {
+            let text = "user::rw-,user:4294967294:r--,group::r-x,mask::r-x,other::---";
+            let value = from_text(text).unwrap();
+            assert_eq!(value.len(), 4 + 5 * 8);
+            let rendered = to_text(&value).unwrap();
+            assert_eq!(from_text(&rendered).unwrap(), value);
+        }
+
+        #[test]
+        fn test_from_text_sorts_and_accepts_star_form() {
+            let sorted = from_text("user::rw-,group::r--,other::r--").unwrap();
+            let shuffled = from_text("other::r--\n# comment\ngroup::r--\nuser::rw-").unwrap();
+            assert_eq!(sorted, shuffled);
+
+            let star = from_text(
+                "user::rw-,user:no-such-user-xyz:r--:1234,group::---,mask::r--,other::---",
+            )
+            .unwrap();
+            assert_eq!(&star[12..20], &[2, 0, 4, 0, 0xd2, 0x04, 0, 0]);
+        }
+
+        #[test]
+        fn test_from_text_rejects_garbage() {
+            assert!(from_text("user::rwz").is_err());
+            assert!(from_text("bogus::r--").is_err());
+            assert!(to_text(&[1, 0, 0, 0]).is_none());
+        }
+    }
+}
@@ -0,0 +1,127 @@
+// This file is part of the uutils tar package.
+//
+// For the full copyright and license information, please view the LICENSE
+// file that was distributed with this source code.
+
+//! Piping extracted files into a command (`--to-command`).
+//!
+//! Like GNU tar, the command is run with the shell once per regular file,
+//! reads the file's data on its standard input and finds the member's
+//! attributes in `TAR_*` environment variables. A command that exits with
+//! a non-zero status is an error unless `--ignore-command-error` is given;
+//! one killed by a signal is only reported.
+
+use crate::member::Member;
+use crate::volume::{self, Volumes};
+use filetime::FileTime;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{ChildStdin, ExitStatus, Stdio};
+
+/// What every run of the command shares
+#[derive(Debug)]
+pub struct ToCommand {
+    /// Shell command to run
+    pub command: String,
+    /// Whether failing commands are not errors (`--ignore-command-error`)
+    pub ignore_errors: bool,
+    /// Stand-in for times the archive does not record
+    pub start_time: FileTime,
+}
+
+impl ToCommand {
+    /// Runs the command for `member`, extracted as `name`, with `write`
+    /// feeding it the member's `size` bytes of data.
+    pub fn run(
+        &self,
+        member: &Member,
+        name: &Path,
+        size: u64,
+        volumes: &Volumes,
+        write: impl FnOnce(&mut ChildStdin) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let (archive, volno) = volumes.current();
+        let mut command = volume::shell_command(&self.command);
+        command
+            .stdin(Stdio::piped())
+            .env("TAR_VERSION", env!("CARGO_PKG_VERSION"))
+            .env("TAR_ARCHIVE", archive)
+            .env("TAR_VOLUME", volno.to_string())
+            .env(
+                "TAR_BLOCKING_FACTOR",
+                volumes.blocking().blocking_factor().to_string(),
+            )
+            .env("TAR_FORMAT", member.format.to_string())
+            .env("TAR_FILETYPE", "f")
+            .env("TAR_MODE", format!("0{:o}", member.mode & 0o7777))
+            .env("TAR_FILENAME", name)
+            .env("TAR_REALNAME", name)
+            .env("TAR_UNAME", member.uname.as_deref().unwrap_or_default())
+            .env("TAR_GNAME", member.gname.as_deref().unwrap_or_default())
+            .env(
+                "TAR_ATIME",
+                format_time(member.atime.unwrap_or(self.start_time)),
+            )
+            .env("TAR_MTIME", format_time(member.mtime))
+            .env("TAR_CTIME", format_time(self.start_time))
+            .env("TAR_SIZE", size.to_string())
+            .env("TAR_UID", member.uid.to_string())
+            .env("TAR_GID", member.gid.to_string());
+
+        let mut child = command.spawn()?;
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let written = write(&mut stdin).and_then(|()| stdin.flush());
+        drop(stdin);
+        let status = child.wait()?;
+        // A command need not read all of its input.
+        match written {
+            Err(e) if e.kind() != io::ErrorKind::BrokenPipe => return Err(e),
+            _ => {}
+        }
+        self.report(child.id(), status);
+        Ok(())
+    }
+
+    /// Reports a command that failed.
+    fn report(&self, pid: u32, status: ExitStatus) {
+        match status.code() {
+            Some(0) => {}
+            Some(code) => {
+                if !self.ignore_errors {
+                    uucore::show_error!("{pid}: Child returned status {code}");
+                    uucore::error::set_exit_code(2);
+                }
+            }
+            None =>
+            {
+                #[cfg(unix)]
+                if let Some(signal) = std::os::unix::process::ExitStatusExt::signal(&status) {
+                    uucore::show_error!("{pid}: Child terminated on signal {signal}");
+                }
+            }
+        }
+    }
+}
+
+/// Formats a time as seconds since the epoch, with nanoseconds if any, for
+/// the `TAR_*TIME` variables.
+fn format_time(time: FileTime) -> String {
+    match time.nanoseconds() {
+        0 => time.unix_seconds().to_string(),
+        nanos => format!("{}.{nanos:09}", time.unix_seconds()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_time() {
+        assert_eq!(format_time(FileTime::from_unix_time(1_000, 0)), "1000");
+        assert_eq!(
+            format_time(FileTime::from_unix_time(1_000, 5)),
+            "1000.000000005"
+        );
+    }
+}
@@ -0,0 +1,86 @@
+// This file is part of the uutils tar package.
+//
+// For the full copyright and license information, please view the LICENSE
+// file that was distributed with this source code.
+
+//! Asking before each member is processed (`-w`).
+//!
+//! As in GNU tar, questions are asked on the controlling terminal, so that
+//! neither an archive piped through standard input nor a listing sent
+//! elsewhere gets in the way. Without a terminal they go to standard error
+//! and the answers are read from standard input a line at a time. Only
+//! answers starting with `y` or `Y` count as yes. Once the input runs out,
+//! every remaining question is answered no.
+
+use std::fs::OpenOptions;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+
+/// Where the questions of `-w` are asked and answered
+#[derive(Debug)]
+pub struct Confirmation<R, W> {
+    input: R,
+    output: W,
+    /// Whether the input has run out
+    eof: bool,
+}
+
+impl Confirmation<Box<dyn BufRead>, Box<dyn Write>> {
+    /// Asks on the controlling terminal, or on standard input and standard
+    /// error when there is none.
+    pub fn open() -> Self {
+        let tty = OpenOptions::new().read(true).write(true).open("/dev/tty");
+        match tty.and_then(|tty| Ok((tty.try_clone()?, tty))) {
+            Ok((input, output)) => Self::new(Box::new(BufReader::new(input)), Box::new(output)),
+            Err(_) => Self::new(Box::new(io::stdin().lock()), Box::new(io::stderr())),
+        }
+    }
+}
+
+impl<R: BufRead, W: Write> Confirmation<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Self {
+            input,
+            output,
+            eof: false,
+        }
+    }
+
+    /// Asks whether to `action` the file `name`, for example
+    /// `extract 'a.txt'?`, and returns the answer.
+    pub fn ask(&mut self, action: &str, name: &Path) -> io::Result<bool> {
+        write!(self.output, "{action} '{}'?", name.display())?;
+        self.output.flush()?;
+
+        let mut answer = Vec::new();
+        if !self.eof && self.input.read_until(b'\n', &mut answer)? == 0 {
+            self.eof = true;
+        }
+        if self.eof {
+            // End the question's line, which no typed answer did.
+            writeln!(self.output)?;
+            self.output.flush()?;
+            return Ok(false);
+        }
+        Ok(matches!(answer.first(), Some(b'y' | b'Y')))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ask() {
+        let mut confirmation = Confirmation::new(&b"y\nno\nYes\n\n"[..], Vec::new());
+        let answers: Vec<bool> = ["a", "b", "c", "d", "e"]
+            .iter()
+            .map(|name| confirmation.ask("add", Path::new(name)).unwrap())
+            .collect();
+        assert_eq!(answers, [true, false, true, false, false]);
+        assert_eq!(
+            String::from_utf8(confirmation.output).unwrap(),
+            "add 'a'?add 'b'?add 'c'?add 'd'?add 'e'?\n"
+        );
+    }
+}
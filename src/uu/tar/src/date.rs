@@ -0,0 +1,157 @@
+// This file is part of the uutils tar package.
+//
+// For the full copyright and license information, please view the LICENSE
+// file that was distributed with this source code.
+
+//! Dates given on the command line (`-N`, `--newer-mtime`).
+//!
+//! A date is either `@` followed by seconds since the epoch, or an ISO 8601
+//! date with an optional time of day, separated by `T` or a space. The time
+//! is local unless it ends in `Z` or a UTC offset such as `+02:00`.
+
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use filetime::FileTime;
+
+/// Layouts accepted for a date and time, without the zone
+const DATE_TIME_FORMATS: [&str; 4] = [
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M",
+];
+
+/// Parses a date, returning `None` if it is not in one of the accepted
+/// formats.
+pub fn parse_date(text: &str) -> Option<FileTime> {
+    let text = text.trim();
+    if let Some(epoch) = text.strip_prefix('@') {
+        return parse_epoch(epoch);
+    }
+
+    let (local, zone) = split_zone(text)?;
+    let naive = DATE_TIME_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(local, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(local, "%Y-%m-%d")
+                .ok()?
+                .and_hms_opt(0, 0, 0)
+        })?;
+    let time = match zone {
+        Some(offset) => offset.from_local_datetime(&naive).single()?.to_utc(),
+        None => Local.from_local_datetime(&naive).earliest()?.to_utc(),
+    };
+    Some(FileTime::from_unix_time(
+        time.timestamp(),
+        time.timestamp_subsec_nanos(),
+    ))
+}
+
+/// Formats `time` the way GNU tar shows dates: `YYYY-MM-DD HH:MM`, or if
+/// `full`, `YYYY-MM-DD HH:MM:SS` followed by the fraction of a second, if
+/// any, without trailing zeros. The time is local unless `utc`, and given
+/// in seconds since the epoch if it is out of range.
+pub fn format_time(time: FileTime, full: bool, utc: bool) -> String {
+    let Some(at) = DateTime::<Utc>::from_timestamp(time.unix_seconds(), 0) else {
+        return time.unix_seconds().to_string();
+    };
+    let layout = if full {
+        "%Y-%m-%d %H:%M:%S"
+    } else {
+        "%Y-%m-%d %H:%M"
+    };
+    let mut text = if utc {
+        at.format(layout).to_string()
+    } else {
+        at.with_timezone(&Local).format(layout).to_string()
+    };
+    if full && time.nanoseconds() != 0 {
+        let fraction = format!("{:09}", time.nanoseconds());
+        text.push('.');
+        text.push_str(fraction.trim_end_matches('0'));
+    }
+    text
+}
+
+/// Parses seconds since the epoch, with an optional fraction.
+fn parse_epoch(text: &str) -> Option<FileTime> {
+    let (seconds, fraction) = text.split_once('.').unwrap_or((text, ""));
+    if fraction.len() > 9 || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let whole: i64 = seconds.parse().ok()?;
+    let nanos: u32 = format!("{fraction:0<9}").parse().ok()?;
+    // The fraction moves negative times further from the epoch.
+    if seconds.starts_with('-') && nanos > 0 {
+        Some(FileTime::from_unix_time(whole - 1, 1_000_000_000 - nanos))
+    } else {
+        Some(FileTime::from_unix_time(whole, nanos))
+    }
+}
+
+/// Splits a trailing `Z` or UTC offset off `text`. The zone is `None` for
+/// local time, and the whole result `None` if the offset is malformed.
+fn split_zone(text: &str) -> Option<(&str, Option<FixedOffset>)> {
+    if let Some(local) = text.strip_suffix(['Z', 'z']) {
+        return Some((local, FixedOffset::east_opt(0)));
+    }
+    // Offsets only follow a time, after the date's own dashes.
+    let Some(sign) = text.rfind(['+', '-']).filter(|&i| i > "YYYY-MM-DD".len()) else {
+        return Some((text, None));
+    };
+    let digits = text[sign + 1..].replace(':', "");
+    if digits.len() != 4 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let hours: i32 = digits[..2].parse().ok()?;
+    let minutes: i32 = digits[2..].parse().ok()?;
+    let seconds = (hours * 60 + minutes) * 60;
+    let offset = if &text[sign..=sign] == "-" {
+        FixedOffset::west_opt(seconds)
+    } else {
+        FixedOffset::east_opt(seconds)
+    }?;
+    Some((text[..sign].trim_end(), Some(offset)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_date() {
+        let at = |secs, nanos| Some(FileTime::from_unix_time(secs, nanos));
+        assert_eq!(parse_date("@1600000000"), at(1_600_000_000, 0));
+        assert_eq!(parse_date("@-5.25"), at(-6, 750_000_000));
+        assert_eq!(parse_date("2021-01-01T00:00:00Z"), at(1_609_459_200, 0));
+        assert_eq!(parse_date("2021-01-01 10:00Z"), at(1_609_495_200, 0));
+        assert_eq!(
+            parse_date("2021-01-01T10:00:00.5+02:00"),
+            at(1_609_488_000, 500_000_000)
+        );
+        assert_eq!(
+            parse_date("2021-01-01 10:00:00 -0130"),
+            at(1_609_500_600, 0)
+        );
+        assert!(parse_date("2021-01-01").is_some());
+        assert_eq!(parse_date("yesterday"), None);
+        assert_eq!(parse_date("2021-13-01"), None);
+        assert_eq!(parse_date("@1.2.3"), None);
+        assert_eq!(parse_date("2021-01-01T10:00+2"), None);
+    }
+
+    #[test]
+    fn test_format_time() {
+        let time = FileTime::from_unix_time(1_609_459_200, 500_000_000);
+        assert_eq!(format_time(time, false, true), "2021-01-01 00:00");
+        assert_eq!(format_time(time, true, true), "2021-01-01 00:00:00.5");
+        assert_eq!(
+            format_time(FileTime::from_unix_time(86_399, 0), true, true),
+            "1970-01-01 23:59:59"
+        );
+        assert_eq!(
+            format_time(FileTime::from_unix_time(i64::MAX, 0), false, true),
+            i64::MAX.to_string()
+        );
+    }
+}
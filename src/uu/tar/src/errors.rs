@@ -3,6 +3,7 @@
 // For the full copyright and license information, please view the LICENSE
 // file that was distributed with this source code.
 
+use crate::format::ArchiveFormat;
 use std::io;
 use std::path::PathBuf;
 use thiserror::Error;
@@ -47,10 +48,92 @@ pub enum TarError {
     #[error("tar: Cannot add file '{path}': {source}")]
     CannotAddFile { path: PathBuf, source: io::Error },
 
+    /// A member name does not fit in the chosen archive format
+    #[error("tar: {path}: file name is too long (max {max})")]
+    FileNameTooLong { path: PathBuf, max: usize },
+
+    /// A member name is too long for the ustar name field and has no `/`
+    /// at which it could be split into the prefix field
+    #[error("tar: {path}: file name is too long (cannot be split)")]
+    FileNameCannotBeSplit { path: PathBuf },
+
+    /// A numeric header field exceeds what the chosen archive format allows
+    #[error("tar: {path}: value {value} out of {kind} range 0..{max}")]
+    ValueOutOfRange {
+        path: PathBuf,
+        kind: &'static str,
+        value: u64,
+        max: u64,
+    },
+
+    /// The chosen archive format has no header type for this kind of file
+    #[error("tar: {path}: file type not supported by the {format} format")]
+    UnsupportedFileType {
+        path: PathBuf,
+        format: ArchiveFormat,
+    },
+
     /// Cannot extract an archive entry
     #[error("tar: Cannot extract '{path}': {source}")]
     CannotExtract { path: PathBuf, source: io::Error },
 
+    /// A file is in the way of a member and may not be replaced
+    #[error("tar: {path}: Cannot open: File exists")]
+    FileExists { path: PathBuf },
+
+    /// Cannot remove a file in the way of a member
+    #[error("tar: {path}: Cannot unlink: {source}")]
+    CannotUnlink { path: PathBuf, source: io::Error },
+
+    /// Cannot rename a file in the way of a member to its backup name
+    #[error("tar: {path}: Was unable to backup this file: {source}")]
+    CannotBackup { path: PathBuf, source: io::Error },
+
+    /// Cannot remove a file left out of an incremental dump
+    #[error("tar: {path}: Cannot remove: {source}")]
+    CannotRemove { path: PathBuf, source: io::Error },
+
+    /// Cannot create a hard link member
+    #[error("tar: {path}: Cannot hard link to '{target}': {source}")]
+    CannotHardLink {
+        path: PathBuf,
+        target: PathBuf,
+        source: io::Error,
+    },
+
+    /// Cannot restore the permissions of an extracted member
+    #[error("tar: {path}: Cannot change mode: {source}")]
+    CannotChangeMode { path: PathBuf, source: io::Error },
+
+    /// Cannot restore the access and modification times of an extracted member
+    #[error("tar: {path}: Cannot utime: {source}")]
+    CannotSetTimes { path: PathBuf, source: io::Error },
+
+    /// The volume label does not match the `-V` pattern
+    #[error("tar: Volume '{label}' does not match '{pattern}'")]
+    LabelMismatch { label: String, pattern: String },
+
+    /// `-V` was given when reading an archive without a volume label
+    #[error("tar: Archive not labeled to match '{pattern}'")]
+    NotLabeled { pattern: String },
+
+    /// Cannot read the snapshot file of an incremental dump
+    #[error("tar: {path}: Cannot read: {source}")]
+    CannotReadSnapshot { path: PathBuf, source: io::Error },
+
+    /// The snapshot file of an incremental dump is not in the version 2
+    /// format
+    #[error("tar: {path}: {reason}")]
+    InvalidSnapshot { path: PathBuf, reason: String },
+
+    /// Cannot write the snapshot file of an incremental dump
+    #[error("tar: {path}: Cannot write: {source}")]
+    CannotWriteSnapshot { path: PathBuf, source: io::Error },
+
+    /// Moving on to the next volume of a multi-volume archive failed
+    #[error("tar: {0}")]
+    VolumeChange(String),
+
     /// Cannot finalize the archive
     #[error("tar: Cannot finalize archive: {0}")]
     CannotFinalizeArchive(io::Error),
@@ -0,0 +1,124 @@
+// This file is part of the uutils tar package.
+//
+// For the full copyright and license information, please view the LICENSE
+// file that was distributed with this source code.
+
+//! Archive formats that can be written (`--format`).
+
+use std::fmt;
+use tar::Header;
+
+/// Largest value of an 8-byte octal header field (uid, gid, mode)
+pub const MAX_OCTAL_ID: u64 = 0o7777777;
+/// Largest value of a 12-byte octal header field (size, mtime)
+pub const MAX_OCTAL_SIZE: u64 = 0o77777777777;
+
+/// Length of the header's name and link name fields
+pub const NAME_FIELD_SIZE: usize = 100;
+/// Length of the ustar header's name prefix field
+pub const PREFIX_FIELD_SIZE: usize = 155;
+
+/// Type flag of a GNU header holding the long name of the next member
+pub const GNU_LONG_NAME: u8 = b'L';
+/// Type flag of a GNU multi-volume continuation header
+pub const GNU_MULTIVOLUME: u8 = b'M';
+/// Type flag of a GNU volume label header
+pub const GNU_VOLUME_LABEL: u8 = b'V';
+/// Type flag of an old GNU sparse file
+pub const GNU_SPARSE: u8 = b'S';
+/// Type flag of a GNU dumpdir, a directory listing its contents
+pub const GNU_DUMPDIR: u8 = b'D';
+
+/// Layout used for the member headers of a new archive
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    /// Unix V7 headers: 99-byte names, no owner names or device files
+    V7,
+    /// GNU headers as written by tar 1.12 and earlier
+    OldGnu,
+    /// GNU headers with `L`/`K` records for long names and base-256
+    /// numbers for large values
+    #[default]
+    Gnu,
+    /// POSIX.1-1988 ustar: names up to 256 bytes split over two fields
+    Ustar,
+    /// POSIX.1-2001 pax: ustar headers plus extended header records for
+    /// anything ustar cannot hold
+    Posix,
+}
+
+impl ArchiveFormat {
+    /// Looks up a format by its `--format` name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "v7" => Some(Self::V7),
+            "oldgnu" => Some(Self::OldGnu),
+            "gnu" => Some(Self::Gnu),
+            "ustar" => Some(Self::Ustar),
+            "posix" | "pax" => Some(Self::Posix),
+            _ => None,
+        }
+    }
+
+    /// Returns an empty header laid out for this format.
+    pub fn new_header(self) -> Header {
+        match self {
+            Self::V7 => Header::new_old(),
+            Self::OldGnu | Self::Gnu => Header::new_gnu(),
+            Self::Ustar | Self::Posix => Header::new_ustar(),
+        }
+    }
+
+    /// Returns whether names and numbers are unbounded because the format
+    /// falls back to GNU extension records.
+    pub fn is_gnu(self) -> bool {
+        matches!(self, Self::OldGnu | Self::Gnu)
+    }
+
+    /// Longest member name the format can store without extension records.
+    pub fn max_name_len(self) -> usize {
+        match self {
+            Self::V7 => NAME_FIELD_SIZE - 1,
+            _ => PREFIX_FIELD_SIZE + 1 + NAME_FIELD_SIZE,
+        }
+    }
+}
+
+/// Reads an octal header field, or a base-256 one as written by GNU tar
+/// for values that do not fit.
+pub fn parse_numeric(field: &[u8]) -> u64 {
+    if field[0] & 0x80 != 0 {
+        return field[1..]
+            .iter()
+            .fold(0u64, |value, &b| (value << 8) | u64::from(b));
+    }
+    field
+        .iter()
+        .skip_while(|&&b| b == b' ')
+        .take_while(|b| (b'0'..=b'7').contains(b))
+        .fold(0, |value, &b| (value << 3) | u64::from(b - b'0'))
+}
+
+impl fmt::Display for ArchiveFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::V7 => "v7",
+            Self::OldGnu => "oldgnu",
+            Self::Gnu => "gnu",
+            Self::Ustar => "ustar",
+            Self::Posix => "posix",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_numeric() {
+        assert_eq!(parse_numeric(b"0000644\0"), 0o644);
+        assert_eq!(parse_numeric(b"   12 \0"), 0o12);
+        assert_eq!(parse_numeric(&[0x80, 0, 0, 0, 0, 0, 0x01, 0x00]), 256);
+    }
+}
@@ -0,0 +1,417 @@
+// This file is part of the uutils tar package.
+//
+// For the full copyright and license information, please view the LICENSE
+// file that was distributed with this source code.
+
+//! GNU incremental archives (`-g`, `-G`).
+//!
+//! An incremental dump starts with a dumpdir member (type `D`) for every
+//! directory, listing the directory's entries, each marked `D` if it is a
+//! subdirectory, `Y` if it is in the archive or `N` if it is not. The files
+//! marked `Y`, those new or changed since the previous dump, follow. With
+//! `-g` a snapshot file in GNU tar's version 2 format records when the dump
+//! was made, along with the device, inode and contents of each directory,
+//! so that the next dump can tell what changed. When extracting, the
+//! dumpdirs tell which files had been deleted by the time of the dump.
+
+use crate::errors::TarError;
+use crate::operations::create::NewerThan;
+use filetime::FileTime;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Version of the snapshot file format that is read and written
+const SNAPSHOT_VERSION: &str = "2";
+
+/// Settings for an incremental dump
+#[derive(Debug, Clone, Default)]
+pub struct IncrementalOptions {
+    /// Snapshot file to compare with and then update (`-g`), if any
+    pub snapshot: Option<PathBuf>,
+    /// Ignore what the snapshot file holds and dump everything
+    /// (`--level=0`)
+    pub level_zero: bool,
+    /// Whether a directory that moved to another device counts as renamed
+    /// (not `--no-check-device`)
+    pub check_device: bool,
+}
+
+/// What a snapshot records about a directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Directory {
+    mtime: FileTime,
+    dev: u64,
+    ino: u64,
+    /// Entries of the directory, each a `D`, `Y` or `N` followed by the
+    /// name and a NUL
+    dumpdir: Vec<u8>,
+}
+
+/// State of an incremental dump in progress
+#[derive(Debug)]
+pub struct Dump {
+    snapshot: Option<PathBuf>,
+    check_device: bool,
+    start_time: FileTime,
+    /// Files older than the previous dump are unchanged, unless their
+    /// directory is new. `None` if there was no previous dump.
+    since: Option<NewerThan>,
+    /// Directories as of the previous dump
+    previous: HashMap<PathBuf, Directory>,
+    /// Directories as of this dump
+    directories: HashMap<PathBuf, Directory>,
+}
+
+impl Dump {
+    /// Starts a dump, reading the snapshot file of the previous one.
+    pub fn new(options: &IncrementalOptions) -> Result<Self, TarError> {
+        let mut dump = Self {
+            snapshot: options.snapshot.clone(),
+            check_device: options.check_device,
+            start_time: FileTime::now(),
+            since: None,
+            previous: HashMap::new(),
+            directories: HashMap::new(),
+        };
+        let Some(path) = options.snapshot.as_ref().filter(|_| !options.level_zero) else {
+            return Ok(dump);
+        };
+        let data = match fs::read(path) {
+            Ok(data) => data,
+            // The first dump creates the snapshot file.
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(dump),
+            Err(e) => {
+                return Err(TarError::CannotReadSnapshot {
+                    path: path.clone(),
+                    source: e,
+                });
+            }
+        };
+        if !data.is_empty() {
+            let (time, previous) =
+                parse_snapshot(&data).map_err(|reason| TarError::InvalidSnapshot {
+                    path: path.clone(),
+                    reason,
+                })?;
+            dump.since = Some(NewerThan {
+                time,
+                mtime_only: false,
+            });
+            dump.previous = previous;
+        }
+        Ok(dump)
+    }
+
+    /// Time before which a file named on the command line is unchanged,
+    /// if there was a previous dump.
+    pub fn newer_than(&self) -> Option<NewerThan> {
+        self.since
+    }
+
+    /// Collects `path` and, if it is a directory, what to archive beneath
+    /// it: every directory, in order of name, then the new and changed
+    /// files of each directory in the same order. With `verbose`, new and
+    /// renamed directories are reported.
+    pub fn scan(&mut self, path: &Path, verbose: bool) -> io::Result<Vec<PathBuf>> {
+        let meta = fs::metadata(path)?;
+        if !meta.is_dir() {
+            return Ok(vec![path.to_path_buf()]);
+        }
+
+        let mut dumped = Vec::new();
+        let all = self.is_new(path, &meta, verbose);
+        self.scan_directory(path, &meta, all, verbose, &mut dumped)?;
+        dumped.sort_by(|(a, _), (b, _)| name_bytes(a).cmp(name_bytes(b)));
+
+        let mut members: Vec<PathBuf> = dumped.iter().map(|(dir, _)| dir.clone()).collect();
+        members.extend(dumped.into_iter().flat_map(|(_, files)| files));
+        Ok(members)
+    }
+
+    /// Returns the contents of the dumpdir member for the directory `path`,
+    /// if it was scanned.
+    pub fn dumpdir(&self, path: &Path) -> Option<Vec<u8>> {
+        self.directories.get(path).map(|directory| {
+            let mut data = directory.dumpdir.clone();
+            data.push(0);
+            data
+        })
+    }
+
+    /// Writes the snapshot file for the next dump, if there is one.
+    pub fn save(&self) -> Result<(), TarError> {
+        let Some(path) = &self.snapshot else {
+            return Ok(());
+        };
+        let mut data =
+            format!("GNU tar-{}-{SNAPSHOT_VERSION}\n", env!("CARGO_PKG_VERSION")).into_bytes();
+        push_time(&mut data, self.start_time);
+
+        let mut directories: Vec<_> = self.directories.iter().collect();
+        directories.sort_by(|(a, _), (b, _)| name_bytes(a).cmp(name_bytes(b)));
+        for (name, directory) in directories {
+            // No directory is treated as being on NFS.
+            push_field(&mut data, b"0");
+            push_time(&mut data, directory.mtime);
+            push_field(&mut data, directory.dev.to_string().as_bytes());
+            push_field(&mut data, directory.ino.to_string().as_bytes());
+            push_field(&mut data, name_bytes(name));
+            data.extend_from_slice(&directory.dumpdir);
+            data.extend_from_slice(b"\0\0");
+        }
+
+        fs::write(path, data).map_err(|e| TarError::CannotWriteSnapshot {
+            path: path.clone(),
+            source: e,
+        })
+    }
+
+    /// Whether every file in the directory `path` is to be dumped, because
+    /// the previous dump did not see the directory there.
+    fn is_new(&self, path: &Path, meta: &Metadata, verbose: bool) -> bool {
+        let (dev, ino) = device_and_inode(meta);
+        let message = match self.previous.get(path) {
+            None => "Directory is new",
+            Some(old) if old.ino != ino || (self.check_device && old.dev != dev) => {
+                "Directory has been renamed"
+            }
+            Some(_) => return false,
+        };
+        if verbose {
+            uucore::show_error!("{}: {message}", path.display());
+        }
+        true
+    }
+
+    /// Records the directory `path` and, beneath it, adds the directories
+    /// and the files to dump from each to `dumped`. Like GNU tar, the
+    /// subdirectories of a directory are all reported as new or renamed
+    /// before any of them is scanned.
+    fn scan_directory(
+        &mut self,
+        path: &Path,
+        meta: &Metadata,
+        all: bool,
+        verbose: bool,
+        dumped: &mut Vec<(PathBuf, Vec<PathBuf>)>,
+    ) -> io::Result<()> {
+        let mut entries = fs::read_dir(path)?
+            .map(|entry| {
+                let entry = entry?;
+                let child = entry.path();
+                // Dangling symbolic links are still dumped.
+                let meta = fs::metadata(&child).or_else(|_| fs::symlink_metadata(&child))?;
+                Ok((entry.file_name(), meta))
+            })
+            .collect::<io::Result<Vec<(OsString, Metadata)>>>()?;
+        entries.sort_by(|(a, _), (b, _)| a.as_encoded_bytes().cmp(b.as_encoded_bytes()));
+
+        let mut dumpdir = Vec::new();
+        let mut files = Vec::new();
+        let mut subdirectories = Vec::new();
+        for (name, child_meta) in entries {
+            let child = path.join(&name);
+            let kind = if child_meta.is_dir() {
+                subdirectories.push((child, child_meta));
+                b'D'
+            } else if all
+                || self
+                    .since
+                    .is_none_or(|since| !since.is_unchanged(&child_meta))
+            {
+                files.push(child);
+                b'Y'
+            } else {
+                b'N'
+            };
+            dumpdir.push(kind);
+            dumpdir.extend_from_slice(name.as_encoded_bytes());
+            dumpdir.push(0);
+        }
+
+        let (dev, ino) = device_and_inode(meta);
+        self.directories.insert(
+            path.to_path_buf(),
+            Directory {
+                mtime: FileTime::from_last_modification_time(meta),
+                dev,
+                ino,
+                dumpdir,
+            },
+        );
+        dumped.push((path.to_path_buf(), files));
+
+        let new: Vec<bool> = subdirectories
+            .iter()
+            .map(|(child, child_meta)| self.is_new(child, child_meta, verbose))
+            .collect();
+        for ((child, child_meta), all) in subdirectories.iter().zip(new) {
+            self.scan_directory(child, child_meta, all, verbose, dumped)?;
+        }
+        Ok(())
+    }
+}
+
+/// Lists the entries of the directory `path` that its dumpdir does not
+/// name, and so were deleted by the time of the dump, in order of name.
+pub fn stale_entries(path: &Path, dumpdir: &[u8]) -> io::Result<Vec<PathBuf>> {
+    let kept: HashSet<&[u8]> = dumpdir
+        .split(|&b| b == 0)
+        .filter_map(|entry| entry.get(1..))
+        .collect();
+    let mut stale: Vec<OsString> = fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.file_name()))
+        .filter(|name| {
+            !name
+                .as_ref()
+                .is_ok_and(|name| kept.contains(name.as_encoded_bytes()))
+        })
+        .collect::<io::Result<_>>()?;
+    stale.sort_by(|a, b| a.as_encoded_bytes().cmp(b.as_encoded_bytes()));
+    Ok(stale.into_iter().map(|name| path.join(name)).collect())
+}
+
+/// Parses a version 2 snapshot file into the time of the dump and the
+/// directories it recorded.
+fn parse_snapshot(data: &[u8]) -> Result<(FileTime, HashMap<PathBuf, Directory>), String> {
+    let newline = data
+        .iter()
+        .position(|&b| b == b'\n')
+        .ok_or("Unexpected EOF in snapshot file")?;
+    let version = std::str::from_utf8(&data[..newline])
+        .ok()
+        .and_then(|line| line.strip_prefix("GNU tar-"))
+        .and_then(|line| line.rsplit_once('-'))
+        .map(|(_, version)| version)
+        .ok_or("Bad incremental file format")?;
+    if version != SNAPSHOT_VERSION {
+        return Err(format!("Unsupported incremental format version: {version}"));
+    }
+
+    // Every record ends in an empty field, followed by a NUL.
+    let rest = &data[newline + 1..];
+    let rest = rest.strip_suffix(b"\0").unwrap_or(rest);
+    let mut fields = rest.split(|&b| b == 0);
+    let time = parse_time(&mut fields)?;
+
+    let mut directories = HashMap::new();
+    while let Some(_nfs) = fields.next() {
+        let mtime = parse_time(&mut fields)?;
+        let dev = parse_number(fields.next())?;
+        let ino = parse_number(fields.next())?;
+        let name = fields.next().ok_or("Unexpected EOF in snapshot file")?;
+        let mut dumpdir = Vec::new();
+        loop {
+            match fields.next() {
+                Some([]) => break,
+                Some(entry) => {
+                    dumpdir.extend_from_slice(entry);
+                    dumpdir.push(0);
+                }
+                None => return Err("Unexpected EOF in snapshot file".to_string()),
+            }
+        }
+        if fields.next() != Some(&[]) {
+            return Err("Unexpected field value in snapshot file".to_string());
+        }
+        let name = PathBuf::from(String::from_utf8_lossy(name).into_owned());
+        directories.insert(
+            name,
+            Directory {
+                mtime,
+                dev,
+                ino,
+                dumpdir,
+            },
+        );
+    }
+    Ok((time, directories))
+}
+
+/// Parses a time stored as seconds and nanoseconds fields.
+fn parse_time<'a>(fields: &mut impl Iterator<Item = &'a [u8]>) -> Result<FileTime, String> {
+    let seconds: i64 = parse_number(fields.next())?;
+    let nanos: u32 = parse_number(fields.next())?;
+    Ok(FileTime::from_unix_time(seconds, nanos))
+}
+
+/// Parses a numeric field.
+fn parse_number<T: std::str::FromStr>(field: Option<&[u8]>) -> Result<T, String> {
+    let field = field.ok_or("Unexpected EOF in snapshot file")?;
+    std::str::from_utf8(field)
+        .ok()
+        .and_then(|text| text.parse().ok())
+        .ok_or_else(|| "Unexpected field value in snapshot file".to_string())
+}
+
+fn push_field(data: &mut Vec<u8>, field: &[u8]) {
+    data.extend_from_slice(field);
+    data.push(0);
+}
+
+fn push_time(data: &mut Vec<u8>, time: FileTime) {
+    push_field(data, time.unix_seconds().to_string().as_bytes());
+    push_field(data, time.nanoseconds().to_string().as_bytes());
+}
+
+fn name_bytes(path: &Path) -> &[u8] {
+    path.as_os_str().as_encoded_bytes()
+}
+
+#[cfg(unix)]
+fn device_and_inode(meta: &Metadata) -> (u64, u64) {
+    use std::os::unix::fs::MetadataExt;
+
+    (meta.dev(), meta.ino())
+}
+
+// Without inode numbers, directories are only known by name.
+#[cfg(not(unix))]
+fn device_and_inode(_meta: &Metadata) -> (u64, u64) {
+    (0, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_snapshot() {
+        let data = b"GNU tar-1.34-2\n100\x005\x00\
+            0\x0090\x000\x007\x0042\x00src\x00Ya\x00Dsub\x00\x00\x00\
+            0\x0080\x001\x007\x0043\x00src/sub\x00\x00\x00";
+        let (time, directories) = parse_snapshot(data).unwrap();
+        assert_eq!(time, FileTime::from_unix_time(100, 5));
+        assert_eq!(directories.len(), 2);
+        assert_eq!(
+            directories[Path::new("src")],
+            Directory {
+                mtime: FileTime::from_unix_time(90, 0),
+                dev: 7,
+                ino: 42,
+                dumpdir: b"Ya\0Dsub\0".to_vec(),
+            }
+        );
+        assert!(directories[Path::new("src/sub")].dumpdir.is_empty());
+
+        assert!(parse_snapshot(b"GNU tar-1.34-2\n100\x005\x00")
+            .unwrap()
+            .1
+            .is_empty());
+        assert_eq!(
+            parse_snapshot(b"GNU tar-1.20-1\n").unwrap_err(),
+            "Unsupported incremental format version: 1"
+        );
+        assert_eq!(
+            parse_snapshot(b"GNU tar-1.34-2\n100\x005\x000\x0090\x00").unwrap_err(),
+            "Unexpected EOF in snapshot file"
+        );
+        assert_eq!(
+            parse_snapshot(b"GNU tar-1.34-2\nlater\x005\x00").unwrap_err(),
+            "Unexpected field value in snapshot file"
+        );
+    }
+}
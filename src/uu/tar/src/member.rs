@@ -0,0 +1,491 @@
+// This file is part of the uutils tar package.
+//
+// For the full copyright and license information, please view the LICENSE
+// file that was distributed with this source code.
+
+//! Member metadata as recorded across all the headers describing it.
+//!
+//! The tar crate consumes PAX (`x`) and GNU long-name (`L`/`K`) headers
+//! itself and only applies part of them, and it returns PAX global (`g`)
+//! headers as ordinary entries. [`HeaderTap`] watches the archive stream as
+//! the tar crate reads it and keeps those headers, so that [`Member`] can
+//! resolve every field with the precedence GNU tar uses: per-file PAX
+//! records, then global PAX records, then GNU long names, then the header.
+
+use crate::format::{parse_numeric, ArchiveFormat, GNU_MULTIVOLUME, GNU_VOLUME_LABEL};
+use crate::pax::{self, PaxRecord};
+use filetime::FileTime;
+use std::cell::RefCell;
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::rc::Rc;
+use tar::{Entry, EntryType, Header};
+
+const BLOCK_SIZE: usize = 512;
+
+/// Metadata of an archive member with all extended headers applied
+#[derive(Debug, Clone)]
+pub struct Member {
+    pub path: PathBuf,
+    pub link_name: Option<PathBuf>,
+    pub entry_type: EntryType,
+    pub mode: u32,
+    pub uid: u64,
+    pub gid: u64,
+    /// Owner name, if the archive records one
+    pub uname: Option<String>,
+    /// Group name, if the archive records one
+    pub gname: Option<String>,
+    pub size: u64,
+    pub mtime: FileTime,
+    /// Access time, if the archive records one
+    pub atime: Option<FileTime>,
+    /// Format of the headers the member was read from
+    pub format: ArchiveFormat,
+    /// Global PAX records in effect followed by the member's own, so later
+    /// records take precedence
+    pub records: Vec<PaxRecord>,
+}
+
+impl Member {
+    /// Returns the value of the PAX record `keyword`, if present.
+    pub fn record(&self, keyword: &str) -> Option<&[u8]> {
+        self.records
+            .iter()
+            .rev()
+            .find(|(key, _)| key == keyword)
+            .map(|(_, value)| value.as_slice())
+    }
+
+    /// Reads the metadata recorded in `header` alone, without any extended
+    /// headers.
+    pub fn from_header(header: &Header) -> io::Result<Member> {
+        // Volume labels and continuation headers leave these empty.
+        let (mode, mtime) = match header.entry_type().as_byte() {
+            GNU_MULTIVOLUME | GNU_VOLUME_LABEL => {
+                let old = header.as_old();
+                (parse_numeric(&old.mode) as u32, parse_numeric(&old.mtime))
+            }
+            _ => (header.mode()?, header.mtime()?),
+        };
+
+        Ok(Member {
+            path: bytes_to_path(&header.path_bytes()),
+            link_name: header.link_name_bytes().map(|link| bytes_to_path(&link)),
+            entry_type: header.entry_type(),
+            mode,
+            // Owners matter less than the data, so a damaged field is not fatal.
+            uid: header.uid().unwrap_or(0),
+            gid: header.gid().unwrap_or(0),
+            uname: non_empty(header.username_bytes()),
+            gname: non_empty(header.groupname_bytes()),
+            size: match header.as_gnu() {
+                Some(gnu) if header.entry_type().is_gnu_sparse() => gnu.real_size()?,
+                _ => header.size()?,
+            },
+            mtime: FileTime::from_unix_time(mtime as i64, 0),
+            atime: header
+                .as_gnu()
+                .and_then(|gnu| gnu.atime().ok())
+                .filter(|&atime| atime != 0)
+                .map(|atime| FileTime::from_unix_time(atime as i64, 0)),
+            format: if header.as_gnu().is_some() {
+                ArchiveFormat::Gnu
+            } else if header.as_ustar().is_some() {
+                ArchiveFormat::Ustar
+            } else {
+                ArchiveFormat::V7
+            },
+            records: Vec::new(),
+        })
+    }
+
+    fn record_str(&self, keyword: &str) -> Option<&str> {
+        self.record(keyword)
+            .and_then(|value| std::str::from_utf8(value).ok())
+    }
+}
+
+/// Extended headers read ahead of a member
+#[derive(Debug, Clone, Default)]
+struct Extensions {
+    records: Vec<PaxRecord>,
+    globals: Vec<PaxRecord>,
+    long_name: Option<Vec<u8>>,
+    long_link: Option<Vec<u8>>,
+}
+
+/// Tracks the header structure of the bytes read so far.
+#[derive(Debug, Default)]
+struct TapState {
+    /// Partial header block
+    block: Vec<u8>,
+    /// Bytes of member data and padding still to pass by
+    skip: u64,
+    /// Extension header being collected: type, bytes left and data so far
+    capture: Option<(u8, u64, Vec<u8>)>,
+    /// Data length of a GNU sparse member whose extension blocks follow
+    sparse_data: Option<u64>,
+    /// Global PAX records in effect
+    globals: Vec<PaxRecord>,
+    /// Extensions for the member whose header has not been seen yet
+    pending: Extensions,
+    /// Extensions for the member whose header was read last
+    current: Extensions,
+}
+
+impl TapState {
+    fn feed(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            if self.skip > 0 {
+                let n = data
+                    .len()
+                    .min(usize::try_from(self.skip).unwrap_or(usize::MAX));
+                self.skip -= n as u64;
+                data = &data[n..];
+                continue;
+            }
+
+            if let Some((_, remaining, buffer)) = &mut self.capture {
+                let n = data
+                    .len()
+                    .min(usize::try_from(*remaining).unwrap_or(usize::MAX));
+                buffer.extend_from_slice(&data[..n]);
+                *remaining -= n as u64;
+                data = &data[n..];
+                if *remaining == 0 {
+                    self.finish_capture();
+                }
+                continue;
+            }
+
+            let n = data.len().min(BLOCK_SIZE - self.block.len());
+            self.block.extend_from_slice(&data[..n]);
+            data = &data[n..];
+            if self.block.len() == BLOCK_SIZE {
+                let block = std::mem::take(&mut self.block);
+                self.process_block(&block);
+            }
+        }
+    }
+
+    fn process_block(&mut self, block: &[u8]) {
+        if let Some(data_len) = self.sparse_data {
+            // GNU sparse extension blocks chain through their last byte.
+            if block[504] == 0 {
+                self.sparse_data = None;
+                self.skip = padded(data_len);
+            }
+            return;
+        }
+        if block.iter().all(|&b| b == 0) {
+            return;
+        }
+
+        let size = parse_numeric(&block[124..136]);
+        match block[156] {
+            kind @ (b'x' | b'g' | b'L' | b'K') => {
+                self.capture = Some((kind, size, Vec::new()));
+                if size == 0 {
+                    self.finish_capture();
+                }
+            }
+            kind => {
+                let mut extensions = std::mem::take(&mut self.pending);
+                extensions.globals.clone_from(&self.globals);
+                let data_len = extensions
+                    .records
+                    .iter()
+                    .rev()
+                    .find(|(key, _)| key == "size")
+                    .and_then(|(_, value)| std::str::from_utf8(value).ok()?.parse().ok())
+                    .unwrap_or(size);
+                self.current = extensions;
+
+                if kind == b'S' && block[482] != 0 {
+                    self.sparse_data = Some(data_len);
+                } else {
+                    self.skip = padded(data_len);
+                }
+            }
+        }
+    }
+
+    fn finish_capture(&mut self) {
+        let Some((kind, _, data)) = self.capture.take() else {
+            return;
+        };
+        self.skip = padded(data.len() as u64) - data.len() as u64;
+
+        match kind {
+            b'x' => self.pending.records.extend(pax::parse_records(&data)),
+            b'g' => {
+                // A global record with an empty value cancels the keyword.
+                for (keyword, value) in pax::parse_records(&data) {
+                    self.globals.retain(|(key, _)| *key != keyword);
+                    if !value.is_empty() {
+                        self.globals.push((keyword, value));
+                    }
+                }
+            }
+            b'L' => self.pending.long_name = Some(trim_nul(data)),
+            b'K' => self.pending.long_link = Some(trim_nul(data)),
+            _ => {}
+        }
+    }
+}
+
+/// Reader wrapped around an archive stream that remembers the extended
+/// headers preceding each member as the tar crate reads past them.
+pub struct HeaderTap<R> {
+    inner: R,
+    state: Rc<RefCell<TapState>>,
+}
+
+impl<R: Read> HeaderTap<R> {
+    /// Wraps `inner`, returning the reader to hand to `tar::Archive` and the
+    /// handle used to resolve the members it yields.
+    pub fn new(inner: R) -> (Self, ExtendedHeaders) {
+        let state = Rc::new(RefCell::new(TapState::default()));
+        let tap = Self {
+            inner,
+            state: Rc::clone(&state),
+        };
+        (tap, ExtendedHeaders(state))
+    }
+
+    /// Returns the wrapped reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for HeaderTap<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.state.borrow_mut().feed(&buf[..n]);
+        Ok(n)
+    }
+}
+
+/// Handle onto the headers seen by a [`HeaderTap`]
+#[derive(Clone)]
+pub struct ExtendedHeaders(Rc<RefCell<TapState>>);
+
+impl ExtendedHeaders {
+    /// Resolves the metadata of `entry`, which must be the entry the archive
+    /// returned last. PAX global headers are not members and should be
+    /// skipped rather than passed here.
+    pub fn member<R: Read>(&self, entry: &Entry<R>) -> io::Result<Member> {
+        let state = self.0.borrow();
+        let extensions = &state.current;
+        let mut member = Member::from_header(entry.header())?;
+        member.records = extensions
+            .globals
+            .iter()
+            .chain(&extensions.records)
+            .cloned()
+            .collect();
+
+        // Sparse members record their real name and size separately, since
+        // the header describes the condensed data.
+        let sparse_name = member.record("GNU.sparse.name");
+        if let Some(path) = sparse_name
+            .or(member.record("path"))
+            .or(extensions.long_name.as_deref())
+        {
+            member.path = bytes_to_path(path);
+        }
+        if let Some(link) = member
+            .record("linkpath")
+            .or(extensions.long_link.as_deref())
+        {
+            member.link_name = Some(bytes_to_path(link));
+        }
+
+        if !member.records.is_empty() {
+            member.format = ArchiveFormat::Posix;
+        }
+        let number = |keyword| member.record_str(keyword)?.parse::<u64>().ok();
+        let (uid, gid) = (number("uid"), number("gid"));
+        let size = number("GNU.sparse.realsize")
+            .or_else(|| number("GNU.sparse.size"))
+            .or_else(|| number("size"));
+        let time = |keyword| member.record_str(keyword).and_then(pax::parse_time);
+        let (mtime, atime) = (time("mtime"), time("atime"));
+        let uname = member.record_str("uname").map(str::to_string);
+        let gname = member.record_str("gname").map(str::to_string);
+
+        member.uid = uid.unwrap_or(member.uid);
+        member.gid = gid.unwrap_or(member.gid);
+        member.size = size.unwrap_or(member.size);
+        member.mtime = mtime.unwrap_or(member.mtime);
+        member.atime = atime.or(member.atime);
+        member.uname = uname.or(member.uname);
+        member.gname = gname.or(member.gname);
+
+        Ok(member)
+    }
+}
+
+/// Rounds `len` up to a whole number of blocks.
+fn padded(len: u64) -> u64 {
+    len.div_ceil(BLOCK_SIZE as u64) * BLOCK_SIZE as u64
+}
+
+fn trim_nul(mut data: Vec<u8>) -> Vec<u8> {
+    if let Some(end) = data.iter().position(|&b| b == 0) {
+        data.truncate(end);
+    }
+    data
+}
+
+fn non_empty(bytes: Option<&[u8]>) -> Option<String> {
+    bytes
+        .filter(|bytes| !bytes.is_empty())
+        .map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+}
+
+#[cfg(unix)]
+fn bytes_to_path(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn bytes_to_path(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use tar::{Archive, Builder, Header};
+
+    fn file_header(size: u64, header: Header) -> Header {
+        let mut header = header;
+        header.set_size(size);
+        header.set_mode(0o644);
+        header.set_mtime(100);
+        header.set_uid(0);
+        header.set_gid(0);
+        header.set_cksum();
+        header
+    }
+
+    fn members(data: Vec<u8>) -> Vec<Member> {
+        let (tap, headers) = HeaderTap::new(Cursor::new(data));
+        let mut archive = Archive::new(tap);
+        let mut members = Vec::new();
+        for entry in archive.entries().unwrap() {
+            let entry = entry.unwrap();
+            if !entry.header().entry_type().is_pax_global_extensions() {
+                members.push(headers.member(&entry).unwrap());
+            }
+        }
+        members
+    }
+
+    fn global_header(builder: &mut Builder<Vec<u8>>, records: &[PaxRecord]) {
+        let data = pax::encode_records(records);
+        let mut header = Header::new_ustar();
+        header.set_entry_type(EntryType::XGlobalHeader);
+        header.set_size(data.len() as u64);
+        header.set_cksum();
+        builder.append(&header, data.as_slice()).unwrap();
+    }
+
+    #[test]
+    fn test_member_formats() {
+        let mut builder = Builder::new(Vec::new());
+        for header in [Header::new_old(), Header::new_ustar(), Header::new_gnu()] {
+            let mut header = file_header(1, header);
+            builder.append_data(&mut header, "f", &b"x"[..]).unwrap();
+        }
+        builder
+            .append_pax_extensions([("mtime", &b"200.5"[..])])
+            .unwrap();
+        let mut header = file_header(1, Header::new_ustar());
+        builder.append_data(&mut header, "f", &b"x"[..]).unwrap();
+
+        let formats: Vec<_> = members(builder.into_inner().unwrap())
+            .iter()
+            .map(|member| member.format)
+            .collect();
+        assert_eq!(
+            formats,
+            [
+                ArchiveFormat::V7,
+                ArchiveFormat::Ustar,
+                ArchiveFormat::Gnu,
+                ArchiveFormat::Posix
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pax_records_override_header() {
+        let mut builder = Builder::new(Vec::new());
+        global_header(
+            &mut builder,
+            &[
+                ("uname".to_string(), b"global".to_vec()),
+                ("gname".to_string(), b"staff".to_vec()),
+            ],
+        );
+        let acl = b"user::rw-\ngroup::r--\nother::r--\n".to_vec();
+        builder
+            .append_pax_extensions([
+                ("SCHILY.acl.access", acl.as_slice()),
+                ("uname", &b"alice"[..]),
+                ("uid", &b"3000000"[..]),
+                ("mtime", &b"1700000000.25"[..]),
+                ("path", &b"real/name.txt"[..]),
+            ])
+            .unwrap();
+        let mut header = file_header(4, Header::new_ustar());
+        builder
+            .append_data(&mut header, "short", &b"data"[..])
+            .unwrap();
+        let mut header = file_header(4, Header::new_ustar());
+        builder
+            .append_data(&mut header, "second", &b"data"[..])
+            .unwrap();
+
+        let members = members(builder.into_inner().unwrap());
+        assert_eq!(members.len(), 2);
+
+        let first = &members[0];
+        assert_eq!(first.path, PathBuf::from("real/name.txt"));
+        assert_eq!(first.uid, 3_000_000);
+        assert_eq!(first.uname.as_deref(), Some("alice"));
+        assert_eq!(first.gname.as_deref(), Some("staff"));
+        assert_eq!(
+            first.mtime,
+            FileTime::from_unix_time(1_700_000_000, 250_000_000)
+        );
+        assert_eq!(first.record("SCHILY.acl.access"), Some(acl.as_slice()));
+
+        // Per-file records end with their member; global ones persist.
+        let second = &members[1];
+        assert_eq!(second.path, PathBuf::from("second"));
+        assert_eq!(second.uname.as_deref(), Some("global"));
+        assert_eq!(second.mtime, FileTime::from_unix_time(100, 0));
+    }
+
+    #[test]
+    fn test_gnu_long_names() {
+        let long = "d/".repeat(80) + "file";
+        let target = "t/".repeat(80) + "target";
+        let mut builder = Builder::new(Vec::new());
+        let mut header = file_header(0, Header::new_gnu());
+        header.set_entry_type(EntryType::Symlink);
+        builder.append_link(&mut header, &long, &target).unwrap();
+
+        let members = members(builder.into_inner().unwrap());
+        assert_eq!(members[0].path, PathBuf::from(&long));
+        assert_eq!(members[0].link_name, Some(PathBuf::from(&target)));
+        assert_eq!(members[0].format, ArchiveFormat::Gnu);
+    }
+}
@@ -3,23 +3,118 @@
 // For the full copyright and license information, please view the LICENSE
 // file that was distributed with this source code.
 
+use crate::acls;
+use crate::confirm::Confirmation;
 use crate::errors::TarError;
-use std::collections::VecDeque;
-use std::fs::{self, File};
-use std::io::{self, BufWriter, Write};
+use crate::format::{ArchiveFormat, GNU_DUMPDIR, MAX_OCTAL_ID, MAX_OCTAL_SIZE, NAME_FIELD_SIZE};
+use crate::incremental::{Dump, IncrementalOptions};
+use crate::member::Member;
+use crate::operations::list::{listing_output, write_block_number, ListingFormat, LongListing};
+use crate::pax::{self, PaxOptions, PaxRecord};
+use crate::selinux;
+use crate::sparse::{self, RegionReader, SparseVersion};
+use crate::volume::{self, Blocking, VolumeOptions, Volumes};
+use crate::xattrs::{self, XattrFilter};
+use filetime::FileTime;
+use std::fs::{self, DirEntry, File, Metadata};
+use std::io::{self, Read, Write};
 use std::path::Component::{self, ParentDir, Prefix, RootDir};
 use std::path::{self, Path, PathBuf};
-use tar::Builder;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tar::{Builder, EntryType, Header, HeaderMode};
 use uucore::error::UResult;
 
+/// Order in which the contents of each directory are added to the archive
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    /// Keep the order in which the filesystem returns directory entries
+    #[default]
+    None,
+    /// Sort entries by name, comparing bytes
+    Name,
+    /// Sort entries by inode number, which reduces seeking on some disks
+    Inode,
+}
+
+/// Time before which files count as unchanged and are left out of the
+/// archive, though directories are always archived
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NewerThan {
+    pub time: FileTime,
+    /// Look only at the modification time (`--newer-mtime`), instead of
+    /// also at the status change time (`-N`)
+    pub mtime_only: bool,
+}
+
+impl NewerThan {
+    /// Whether the file described by `meta` is to be left out.
+    pub fn is_unchanged(&self, meta: &Metadata) -> bool {
+        !meta.is_dir()
+            && FileTime::from_last_modification_time(meta) < self.time
+            && (self.mtime_only || status_change_time(meta) < self.time)
+    }
+}
+
+/// Options controlling how an archive is created
+#[derive(Debug, Clone, Default)]
+pub struct CreateOptions {
+    /// Allow absolute paths while creating archive
+    pub allow_absolute: bool,
+    /// Order in which directory contents are archived
+    pub sort: SortOrder,
+    /// Produce byte-for-byte identical output for identical input trees
+    ///
+    /// Members are sorted by name, owners are reset to root with no user or
+    /// group names, and modes are normalized to `0755`/`0644`.
+    pub reproducible: bool,
+    /// Upper bound for member modification times (usually `SOURCE_DATE_EPOCH`)
+    pub mtime_clamp: Option<u64>,
+    /// Store extended attributes passing this filter (`--xattrs`)
+    pub xattrs: Option<XattrFilter>,
+    /// Store POSIX ACLs (`--acls`)
+    pub acls: bool,
+    /// Store SELinux contexts (`--selinux`)
+    pub selinux: bool,
+    /// Header layout to write (`--format`)
+    pub format: ArchiveFormat,
+    /// Extended header settings (`--pax-option`)
+    pub pax: PaxOptions,
+    /// Store only the data of files with holes, using this encoding in
+    /// POSIX archives (`-S`, `--sparse-version`)
+    pub sparse: Option<SparseVersion>,
+    /// Record size to write the archive in
+    pub blocking: Blocking,
+    /// Split the archive over several volumes (`-M`)
+    pub multi_volume: Option<VolumeOptions>,
+    /// Volume label to record at the start of the archive (`-V`)
+    pub label: Option<String>,
+    /// Leave out files not changed since a given time (`-N`,
+    /// `--newer-mtime`)
+    pub newer: Option<NewerThan>,
+    /// Make an incremental dump (`-g`, `-G`)
+    pub incremental: Option<IncrementalOptions>,
+    /// Ask before processing each member (`-w`)
+    pub interactive: bool,
+    /// List the archived members: by name if 1, in full if 2 or more
+    /// (`-v`)
+    pub verbose: u8,
+    /// How a full listing shows owners and dates
+    pub listing: ListingFormat,
+    /// Start each line about a member with the block its header is in
+    /// (`-R`)
+    pub block_number: bool,
+    /// File to write the listing to instead of standard output
+    /// (`--index-file`)
+    pub index_file: Option<PathBuf>,
+}
+
 /// Create a tar archive from the specified files
 ///
 /// # Arguments
 ///
 /// * `archive_path` - Path where the tar archive should be created
 /// * `files` - Slice of file paths to add to the archive
-/// * `allow_absolute` - Allow absolute paths while creating archive
-/// * `verbose` - Whether to print verbose output during creation
+/// * `options` - Options controlling how members are written
 ///
 /// # Errors
 ///
@@ -30,9 +125,16 @@ use uucore::error::UResult;
 pub fn create_archive(
     archive_path: &Path,
     files: &[&Path],
-    allow_absolute: bool,
-    verbose: bool,
+    options: &CreateOptions,
 ) -> UResult<()> {
+    let allow_absolute = options.allow_absolute;
+    let verbose = options.verbose > 0;
+    let sort = if options.reproducible {
+        SortOrder::Name
+    } else {
+        options.sort
+    };
+
     // Create the output file
     let file = File::create(archive_path).map_err(|e| TarError::CannotCreateArchive {
         path: archive_path.to_path_buf(),
@@ -40,10 +142,46 @@ pub fn create_archive(
     })?;
 
     // Create Builder instance
-    let mut builder = Builder::new(file);
+    let volumes = Volumes::new(
+        archive_path,
+        options.blocking,
+        options.multi_volume.as_ref(),
+        "-c",
+        options.format,
+    )?;
+    // POSIX archives keep the label in the global header instead.
+    let (label, label_records) = match &options.label {
+        Some(label) if options.format == ArchiveFormat::Posix => (
+            None,
+            vec![(volume::LABEL_KEYWORD.to_string(), label.as_bytes().to_vec())],
+        ),
+        label => (label.as_deref(), Vec::new()),
+    };
+    // Reproducible archives date their own headers from the clamp, or the
+    // epoch, rather than the clock.
+    let header_mtime = options.mtime_clamp.or(options.reproducible.then_some(0));
+    let mut builder = Builder::new(volumes.writer(file, label, header_mtime));
     builder.preserve_absolute(allow_absolute);
 
-    let mut out = BufWriter::new(io::stdout().lock());
+    // `--pax-option keyword=value` records go in one global header up front.
+    let mut global_records = label_records;
+    global_records.extend(options.pax.global_records());
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    append_extended_header(
+        &mut builder,
+        EntryType::XGlobalHeader,
+        &options.pax.global_header_name(1, options.reproducible),
+        options.pax.global_header_mtime(header_mtime.unwrap_or(now)),
+        &global_records,
+    )
+    .map_err(|e| volumes.explain(TarError::Io(e)))?;
+
+    let mut out = listing_output(options.index_file.as_deref(), Box::new(io::stdout().lock()))?;
+    let mut confirmation = options.interactive.then(Confirmation::open);
+    let mut dump = options.incremental.as_ref().map(Dump::new).transpose()?;
+    let mut listing = (options.verbose > 1).then(|| LongListing::new(options.listing));
 
     // Add each file or directory to the archive
     for &path in files {
@@ -55,22 +193,6 @@ pub fn create_archive(
             .into());
         }
 
-        if verbose {
-            let to_print = get_tree(path)?
-                .iter()
-                .map(|p| (p.is_dir(), p.display().to_string()))
-                .map(|(is_dir, path)| {
-                    if is_dir {
-                        format!("{}{}", path, path::MAIN_SEPARATOR)
-                    } else {
-                        path
-                    }
-                })
-                .collect::<Vec<_>>()
-                .join("\n");
-            writeln!(out, "{to_print}").map_err(TarError::Io)?;
-        }
-
         // Normalize path if needed (so far, handles only absolute paths)
         let normalized_name = if let Some(normalized) = normalize_path(path, allow_absolute) {
             let original_components: Vec<Component> = path.components().collect();
@@ -93,50 +215,503 @@ pub fn create_archive(
             path.to_path_buf()
         };
 
-        // If it's a directory, recursively add all contents
-        if path.is_dir() {
-            builder.append_dir_all(normalized_name, path).map_err(|e| {
-                TarError::CannotAddDirectory {
-                    path: path.to_path_buf(),
-                    source: e,
+        // Walk the tree ourselves rather than using `append_dir_all`, so the
+        // requested member order is used for both the archive and the listing.
+        let tree = match dump.as_mut() {
+            Some(dump) => dump.scan(path, verbose),
+            None => get_tree(path, sort),
+        }
+        .map_err(|e| TarError::CannotAddDirectory {
+            path: path.to_path_buf(),
+            source: e,
+        })?;
+
+        // A declined directory is left out along with its contents, which
+        // follow it in the tree.
+        let mut declined: Option<&Path> = None;
+        for member in &tree {
+            if declined.is_some_and(|dir| member.starts_with(dir)) {
+                continue;
+            }
+            if let Some(confirmation) = confirmation.as_mut() {
+                out.flush().map_err(TarError::Io)?;
+                if !confirmation.ask("add", member).map_err(TarError::Io)? {
+                    declined = Some(member);
+                    continue;
                 }
-            })?;
-        } else {
-            // For files, add them directly
-            builder
-                .append_path_with_name(path, normalized_name)
-                .map_err(|e| TarError::CannotAddFile {
-                    path: path.to_path_buf(),
-                    source: e,
-                })?;
+            }
+
+            // Files beneath a directory in an incremental dump were chosen
+            // by comparing them with the snapshot.
+            let newer = match &dump {
+                Some(_) if member != path => None,
+                Some(dump) => options.newer.or(dump.newer_than()),
+                None => options.newer,
+            };
+            if let Some(newer) = newer {
+                if fs::metadata(member).is_ok_and(|meta| newer.is_unchanged(&meta)) {
+                    if verbose {
+                        uucore::show_error!("{}: file is unchanged; not dumped", member.display());
+                    }
+                    continue;
+                }
+            }
+
+            let is_dir = member.is_dir();
+            let shown_name = if is_dir {
+                format!("{}{}", member.display(), path::MAIN_SEPARATOR)
+            } else {
+                member.display().to_string()
+            };
+
+            let block = builder.get_ref().block();
+            // The full listing describes the header, once it is written.
+            if verbose && listing.is_none() {
+                write_block_number(&mut out, options.block_number, block).map_err(TarError::Io)?;
+                writeln!(out, "{shown_name}").map_err(TarError::Io)?;
+            }
+
+            let name = match member.strip_prefix(path) {
+                Ok(relative) if relative.as_os_str().is_empty() => normalized_name.clone(),
+                Ok(relative) => normalized_name.join(relative),
+                Err(_) => member.clone(),
+            };
+
+            let header = match dump.as_ref().and_then(|dump| dump.dumpdir(member)) {
+                Some(dumpdir) => append_dumpdir(&mut builder, member, &name, &dumpdir, options),
+                None => append_member(&mut builder, member, &name, options),
+            }
+            .map_err(|e| volumes.explain(e))?;
+
+            if let Some(listing) = listing.as_mut() {
+                let mut described = Member::from_header(&header).map_err(TarError::Io)?;
+                described.path = PathBuf::from(shown_name);
+                let line = listing.member_line(&described, &header);
+                write_block_number(&mut out, options.block_number, block).map_err(TarError::Io)?;
+                writeln!(out, "{line}").map_err(TarError::Io)?;
+            }
         }
     }
 
     // Finish writing the archive
     out.flush().map_err(TarError::Io)?;
-    builder.finish().map_err(TarError::CannotFinalizeArchive)?;
+    builder
+        .into_inner()
+        .and_then(|writer| writer.finish())
+        .map_err(|e| volumes.explain(TarError::CannotFinalizeArchive(e)))?;
+    volumes.finish()?;
+    if let Some(dump) = dump {
+        dump.save()?;
+    }
 
     Ok(())
 }
 
-fn get_tree(path: &Path) -> Result<Vec<PathBuf>, std::io::Error> {
+/// Appends a single filesystem object at `src` to the archive as `name`.
+///
+/// Symbolic links are followed, so the member describes the file they point
+/// to. Directory contents are not added; see [`get_tree`].
+fn append_member<W: Write>(
+    builder: &mut Builder<W>,
+    src: &Path,
+    name: &Path,
+    options: &CreateOptions,
+) -> Result<Header, TarError> {
+    let meta = fs::metadata(src).map_err(|e| add_error(src, false, e))?;
+    let fail = |e| add_error(src, meta.is_dir(), e);
+
+    if options.format == ArchiveFormat::V7 && !meta.is_file() && !meta.is_dir() {
+        return Err(TarError::UnsupportedFileType {
+            path: src.to_path_buf(),
+            format: options.format,
+        });
+    }
+
+    let mut header = member_header(&meta, options);
+
+    let mut pax_records = Vec::new();
+    if let Some(filter) = &options.xattrs {
+        pax_records.extend(xattrs::read_pax_records(src, filter).map_err(fail)?);
+    }
+    if options.acls {
+        pax_records.extend(acls::read_pax_records(src).map_err(fail)?);
+    }
+    if options.selinux {
+        pax_records.extend(selinux::read_pax_record(src).map_err(fail)?);
+    }
+
+    if meta.is_file() {
+        let mut file = File::open(src).map_err(fail)?;
+        // Only the GNU and POSIX formats can describe holes.
+        let sparse_map = match options.sparse {
+            Some(_) if options.format.is_gnu() || options.format == ArchiveFormat::Posix => {
+                sparse::scan(&mut file, &meta).map_err(fail)?
+            }
+            _ => None,
+        };
+        let Some(map) = sparse_map else {
+            return write_member(builder, &mut header, src, name, pax_records, file, options);
+        };
+
+        let regions = RegionReader::new(file, &map);
+        if options.format.is_gnu() {
+            let extension_blocks = sparse::set_gnu_header(&mut header, &map);
+            let data = extension_blocks.as_slice().chain(regions);
+            return write_member(builder, &mut header, src, name, pax_records, data, options);
+        }
+
+        let version = options.sparse.unwrap_or_default();
+        pax_records.extend(sparse::pax_records(&map, version, name));
+        header.set_size(map.stored_size());
+        if version != SparseVersion::V1_0 {
+            return write_member(
+                builder,
+                &mut header,
+                src,
+                name,
+                pax_records,
+                regions,
+                options,
+            );
+        }
+        // Other tars see the condensed data, map included, under another name.
+        let map_block = sparse::map_block(&map);
+        header.set_size(map_block.len() as u64 + map.stored_size());
+        let data = map_block.as_slice().chain(regions);
+        let stored_name = PathBuf::from(sparse::member_name(name, options.reproducible));
+        write_member(
+            builder,
+            &mut header,
+            src,
+            &stored_name,
+            pax_records,
+            data,
+            options,
+        )
+    } else {
+        if !meta.is_dir() {
+            set_special(&mut header, src, &meta).map_err(fail)?;
+        }
+        write_member(
+            builder,
+            &mut header,
+            src,
+            name,
+            pax_records,
+            io::empty(),
+            options,
+        )
+    }
+}
+
+/// Appends the directory at `src` to an incremental dump as the dumpdir
+/// member `name`, whose data lists the directory's contents.
+fn append_dumpdir<W: Write>(
+    builder: &mut Builder<W>,
+    src: &Path,
+    name: &Path,
+    dumpdir: &[u8],
+    options: &CreateOptions,
+) -> Result<Header, TarError> {
+    let meta = fs::metadata(src).map_err(|e| add_error(src, true, e))?;
+    let mut header = member_header(&meta, options);
+    header.set_entry_type(EntryType::new(GNU_DUMPDIR));
+    header.set_size(dumpdir.len() as u64);
+    // The trailing slash is how other tars know the member is a directory.
+    write_member(
+        builder,
+        &mut header,
+        src,
+        &name.join(""),
+        Vec::new(),
+        dumpdir,
+        options,
+    )
+}
+
+/// Wraps an I/O error raised while archiving `path`.
+fn add_error(path: &Path, is_dir: bool, source: io::Error) -> TarError {
+    let path = path.to_path_buf();
+    if is_dir {
+        TarError::CannotAddDirectory { path, source }
+    } else {
+        TarError::CannotAddFile { path, source }
+    }
+}
+
+/// Builds the header describing `meta`, applying the reproducibility rules
+/// from `options`.
+fn member_header(meta: &Metadata, options: &CreateOptions) -> Header {
+    let mut header = options.format.new_header();
+
+    if options.reproducible {
+        // Deterministic mode zeroes the owner and normalizes the mode, but
+        // pins the mtime to a fixed date; restore the real (clamped) one.
+        header.set_metadata_in_mode(meta, HeaderMode::Deterministic);
+        header.set_mtime(modified_secs(meta));
+    } else {
+        header.set_metadata_in_mode(meta, HeaderMode::Complete);
+    }
+
+    if let Some(clamp) = options.mtime_clamp {
+        header.set_mtime(header.mtime().unwrap_or(0).min(clamp));
+    }
+
+    // Like GNU tar, incremental dumps record the other times too.
+    if options.incremental.is_some() {
+        if let Some(gnu) = header.as_gnu_mut() {
+            let seconds = |time: FileTime| time.unix_seconds().max(0) as u64;
+            gnu.set_atime(seconds(FileTime::from_last_access_time(meta)));
+            gnu.set_ctime(seconds(status_change_time(meta)));
+        }
+    }
+
+    header
+}
+
+/// Writes `header`, its PAX records and `data` as the member `name`,
+/// returning the header as written.
+///
+/// The GNU formats leave long names and large numbers to the tar crate,
+/// which emits `L` records and base-256 fields. The other formats must fit
+/// the ustar fields: `posix` moves whatever does not fit into PAX records
+/// (unless `--pax-option delete=` removes them), while `v7` and `ustar`
+/// reject the member.
+fn write_member<W: Write, R: Read>(
+    builder: &mut Builder<W>,
+    header: &mut Header,
+    src: &Path,
+    name: &Path,
+    mut pax_records: Vec<PaxRecord>,
+    data: R,
+    options: &CreateOptions,
+) -> Result<Header, TarError> {
+    let format = options.format;
+    let is_dir = header.entry_type().is_dir();
+    let fail = |e| add_error(src, is_dir, e);
+    let mtime = header.mtime().map_err(fail)?;
+    let append_records = |builder: &mut Builder<W>, records: &mut Vec<PaxRecord>| {
+        options.pax.apply(records);
+        append_extended_header(
+            builder,
+            EntryType::XHeader,
+            &options.pax.extended_header_name(name, options.reproducible),
+            options.pax.extended_header_mtime(mtime),
+            records,
+        )
+        .map_err(fail)
+    };
+
+    if format.is_gnu() {
+        append_records(builder, &mut pax_records)?;
+        builder.append_data(header, name, data).map_err(fail)?;
+        return Ok(header.clone());
+    }
+
+    let set_path = |header: &mut Header, path: &Path| {
+        if options.allow_absolute {
+            header.set_path_absolute(path)
+        } else {
+            header.set_path(path)
+        }
+    };
+
+    let name_bytes = name.as_os_str().as_encoded_bytes();
+    if format != ArchiveFormat::Posix && name_bytes.len() > format.max_name_len() {
+        return Err(TarError::FileNameTooLong {
+            path: src.to_path_buf(),
+            max: format.max_name_len(),
+        });
+    }
+    if let Err(e) = set_path(header, name) {
+        if name_bytes.len() <= NAME_FIELD_SIZE {
+            return Err(fail(e));
+        }
+        if format != ArchiveFormat::Posix || options.pax.is_deleted("path") {
+            return Err(TarError::FileNameCannotBeSplit {
+                path: src.to_path_buf(),
+            });
+        }
+        pax_records.push(("path".to_string(), name_bytes.to_vec()));
+        set_path(header, &truncated_name(name)).map_err(fail)?;
+    }
+
+    let fields = [
+        ("uid", "uid_t", header.uid(), MAX_OCTAL_ID),
+        ("gid", "gid_t", header.gid(), MAX_OCTAL_ID),
+        ("size", "off_t", header.size(), MAX_OCTAL_SIZE),
+        ("mtime", "time_t", header.mtime(), MAX_OCTAL_SIZE),
+    ];
+    for (key, kind, value, max) in fields {
+        let value = value.map_err(fail)?;
+        if value <= max {
+            continue;
+        }
+        if format != ArchiveFormat::Posix || options.pax.is_deleted(key) {
+            return Err(TarError::ValueOutOfRange {
+                path: src.to_path_buf(),
+                kind,
+                value,
+                max,
+            });
+        }
+        pax_records.push((key.to_string(), value.to_string().into_bytes()));
+    }
+    // The tar crate stores oversized values in base-256, which is not
+    // valid ustar; readers take them from the PAX records instead.
+    for (key, _) in &pax_records {
+        match key.as_str() {
+            "uid" => header.set_uid(0),
+            "gid" => header.set_gid(0),
+            "size" => header.set_size(0),
+            "mtime" => header.set_mtime(0),
+            _ => {}
+        }
+    }
+
+    append_records(builder, &mut pax_records)?;
+    header.set_cksum();
+    builder.append(header, data).map_err(fail)?;
+    Ok(header.clone())
+}
+
+/// Appends an extended header of type `entry_type` holding `records`, if
+/// there are any.
+fn append_extended_header<W: Write>(
+    builder: &mut Builder<W>,
+    entry_type: EntryType,
+    name: &str,
+    mtime: u64,
+    records: &[PaxRecord],
+) -> io::Result<()> {
+    if records.is_empty() {
+        return Ok(());
+    }
+    let data = pax::encode_records(records);
+
+    let mut header = Header::new_ustar();
+    // Header names are informational only, so long ones are cut short.
+    let field = &mut header.as_old_mut().name;
+    let len = name.len().min(field.len());
+    field[..len].copy_from_slice(&name.as_bytes()[..len]);
+    header.set_entry_type(entry_type);
+    header.set_mode(0o644);
+    header.set_uid(0);
+    header.set_gid(0);
+    header.set_mtime(mtime.min(MAX_OCTAL_SIZE));
+    header.set_size(data.len() as u64);
+    header.set_cksum();
+    builder.append(&header, data.as_slice())
+}
+
+/// Shortens `name` to something that fits the ustar name field, for headers
+/// whose real name is carried by a PAX `path` record.
+fn truncated_name(name: &Path) -> PathBuf {
+    let file_name = name.file_name().unwrap_or(name.as_os_str());
+    let mut short = file_name.to_string_lossy().into_owned();
+    while short.len() > NAME_FIELD_SIZE {
+        short.pop();
+    }
+    PathBuf::from(short)
+}
+
+/// Seconds since the epoch at which `meta` was last modified
+fn modified_secs(meta: &Metadata) -> u64 {
+    meta.modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Marks `header` as a device node or FIFO, recording its device numbers.
+#[cfg(unix)]
+fn set_special(header: &mut Header, src: &Path, meta: &Metadata) -> io::Result<()> {
+    use std::os::unix::fs::{FileTypeExt, MetadataExt};
+
+    let file_type = meta.file_type();
+    let entry_type = if file_type.is_fifo() {
+        EntryType::Fifo
+    } else if file_type.is_char_device() {
+        EntryType::Char
+    } else if file_type.is_block_device() {
+        EntryType::Block
+    } else {
+        return Err(io::Error::other(format!(
+            "{}: socket can not be archived",
+            src.display()
+        )));
+    };
+
+    header.set_entry_type(entry_type);
+    header.set_size(0);
+    let dev = meta.rdev();
+    header.set_device_major(((dev >> 32) & 0xffff_f000 | (dev >> 8) & 0x0000_0fff) as u32)?;
+    header.set_device_minor(((dev >> 12) & 0xffff_ff00 | dev & 0x0000_00ff) as u32)
+}
+
+#[cfg(not(unix))]
+fn set_special(_header: &mut Header, src: &Path, _meta: &Metadata) -> io::Result<()> {
+    Err(io::Error::other(format!(
+        "{} has unknown file type",
+        src.display()
+    )))
+}
+
+#[cfg(unix)]
+fn status_change_time(meta: &Metadata) -> FileTime {
+    use std::os::unix::fs::MetadataExt;
+
+    FileTime::from_unix_time(meta.ctime(), meta.ctime_nsec() as u32)
+}
+
+// There is no status change time; changes to the data will have to do.
+#[cfg(not(unix))]
+fn status_change_time(meta: &Metadata) -> FileTime {
+    FileTime::from_last_modification_time(meta)
+}
+
+/// Collects `path` and, if it is a directory, everything beneath it in the
+/// order the members should be archived: each directory is followed by its
+/// children, which are ordered according to `sort`.
+fn get_tree(path: &Path, sort: SortOrder) -> Result<Vec<PathBuf>, std::io::Error> {
     let mut paths = Vec::new();
-    let mut stack = VecDeque::new();
-    stack.push_back(path.to_path_buf());
+    let mut stack = vec![path.to_path_buf()];
 
-    while let Some(current) = stack.pop_back() {
-        paths.push(current.clone());
+    while let Some(current) = stack.pop() {
         if current.is_dir() {
-            for entry in fs::read_dir(current)? {
-                let child = entry?.path();
-                stack.push_back(child);
-            }
+            let mut children = fs::read_dir(&current)?.collect::<Result<Vec<_>, _>>()?;
+            sort_dir_entries(&mut children, sort);
+            // Push in reverse so the first child is popped (and archived) first.
+            stack.extend(children.iter().rev().map(|entry| entry.path()));
         }
+        paths.push(current);
     }
 
     Ok(paths)
 }
 
+/// Orders the entries of a single directory.
+fn sort_dir_entries(entries: &mut [DirEntry], sort: SortOrder) {
+    match sort {
+        SortOrder::None => {}
+        SortOrder::Name => {
+            entries.sort_by(|a, b| {
+                a.file_name()
+                    .as_encoded_bytes()
+                    .cmp(b.file_name().as_encoded_bytes())
+            });
+        }
+        #[cfg(unix)]
+        SortOrder::Inode => {
+            use std::os::unix::fs::DirEntryExt;
+            entries.sort_by_key(DirEntryExt::ino);
+        }
+        // Inode numbers are not available here; keep the directory order.
+        #[cfg(not(unix))]
+        SortOrder::Inode => {}
+    }
+}
+
 fn normalize_path(path: &Path, allow_absolute: bool) -> Option<PathBuf> {
     if path.is_absolute() && !allow_absolute {
         Some(
@@ -3,19 +3,150 @@
 // For the full copyright and license information, please view the LICENSE
 // file that was distributed with this source code.
 
+use crate::acls::{self, ArchivedAcls};
+use crate::command::ToCommand;
+use crate::confirm::Confirmation;
 use crate::errors::TarError;
-use std::fs::File;
-use std::io::{self, BufWriter, Write};
-use std::path::Path;
-use tar::Archive;
+use crate::format::{ArchiveFormat, GNU_DUMPDIR, GNU_MULTIVOLUME, GNU_VOLUME_LABEL};
+use crate::incremental;
+use crate::member::{HeaderTap, Member};
+use crate::operations::list::{
+    listing_output, write_archive_end, write_block_number, ListingFormat, LongListing,
+};
+use crate::safety::SafeNames;
+use crate::selinux;
+use crate::sparse::{self, SparseMap};
+use crate::volume::{self, Blocking, VolumeOptions, Volumes, BLOCK_SIZE};
+use crate::xattrs::{self, XattrFilter};
+use filetime::FileTime;
+use std::cmp::Reverse;
+use std::fs::{self, File};
+use std::io::{self, BufRead, Read, Write};
+use std::path::{Path, PathBuf};
+use tar::{Archive, Entry, EntryType};
+use uucore::backup_control::{self, BackupMode};
 use uucore::error::UResult;
 
+/// What to do about files already present where members are extracted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OldFiles {
+    /// Replace existing files, merging directories into existing ones
+    /// (also `--overwrite`)
+    #[default]
+    Overwrite,
+    /// Leave existing files alone and report them as errors (`-k`)
+    Keep,
+    /// Leave existing files alone without complaint (`--skip-old-files`)
+    Skip,
+    /// Leave existing files alone if they are at least as new as the
+    /// archived ones (`--keep-newer-files`)
+    KeepNewer,
+    /// Remove each file before extracting over it, directories included
+    /// (`-U`)
+    UnlinkFirst,
+}
+
+/// Options controlling how an archive is extracted
+#[derive(Debug, Clone, Default)]
+pub struct ExtractOptions {
+    /// Leave extracted members with the current time instead of restoring
+    /// their archived modification time (`-m`)
+    pub touch: bool,
+    /// Also restore archived access times, when the archive records them
+    pub atime_preserve: bool,
+    /// Restore extended attributes passing this filter (`--xattrs`)
+    pub xattrs: Option<XattrFilter>,
+    /// Restore POSIX ACLs (`--acls`)
+    pub acls: bool,
+    /// Restore SELinux contexts (`--selinux`)
+    pub selinux: bool,
+    /// Record size to read the archive in
+    pub blocking: Blocking,
+    /// Read an archive split over several volumes (`-M`)
+    pub multi_volume: Option<VolumeOptions>,
+    /// Glob the archive's volume label must match (`-V`)
+    pub label: Option<String>,
+    /// Read past zero blocks instead of stopping at the first (`-i`)
+    pub ignore_zeros: bool,
+    /// Extract member names as they are, even absolute or containing `..`
+    /// (`-P`)
+    pub absolute_names: bool,
+    /// What to do about existing files
+    pub old_files: OldFiles,
+    /// Remove existing directory trees in the way of members
+    /// (`--recursive-unlink`)
+    pub recursive_unlink: bool,
+    /// Leave symbolic links to directories in place of directory members
+    /// (`--keep-directory-symlink`)
+    pub keep_directory_symlink: bool,
+    /// Write the data of regular files to standard output instead of disk
+    /// (`-O`)
+    pub to_stdout: bool,
+    /// Pipe the data of regular files into this shell command instead of
+    /// writing them to disk (`--to-command`)
+    pub to_command: Option<String>,
+    /// Don't treat a failing `--to-command` as an error
+    /// (`--ignore-command-error`)
+    pub ignore_command_error: bool,
+    /// How to back up existing files before replacing them (`--backup`)
+    pub backup: BackupMode,
+    /// Suffix of simple backups (`--suffix`)
+    pub backup_suffix: String,
+    /// Delete files that a dumpdir member does not list (`-G`, `-g`)
+    pub incremental: bool,
+    /// Ask before processing each member (`-w`)
+    pub interactive: bool,
+    /// List the extracted members: by name if 1, in full if 2 or more
+    /// (`-v`)
+    pub verbose: u8,
+    /// How a full listing shows owners and dates
+    pub listing: ListingFormat,
+    /// Start each line about a member with the block its header is in
+    /// (`-R`)
+    pub block_number: bool,
+    /// File to write the listing to instead of standard output
+    /// (`--index-file`)
+    pub index_file: Option<PathBuf>,
+}
+
+/// Access and modification times to apply to an extracted member
+#[derive(Debug, Clone, Copy)]
+struct MemberTimes {
+    atime: FileTime,
+    mtime: FileTime,
+}
+
+/// Metadata of an extracted directory that is applied once every member has
+/// been written, like GNU tar's delayed `set_stat` list. Applying it
+/// immediately would let a read-only directory block its own contents, and
+/// writing the contents would clobber the directory's mtime.
+#[derive(Debug)]
+struct DelayedDirectory {
+    path: PathBuf,
+    mode: u32,
+    times: Option<MemberTimes>,
+    acls: ArchivedAcls,
+}
+
+/// A symbolic link that could lead outside the extraction directory. An
+/// empty placeholder file stands in for it until every member has been
+/// extracted, so that no later member is written through it.
+#[derive(Debug)]
+struct DelayedLink {
+    path: PathBuf,
+    target: PathBuf,
+    /// Metadata of the placeholder, to tell whether a later member
+    /// replaced it
+    placeholder: fs::Metadata,
+    times: Option<MemberTimes>,
+}
+
 /// Extract files from a tar archive
 ///
 /// # Arguments
 ///
 /// * `archive_path` - Path to the tar archive to extract
-/// * `verbose` - Whether to print verbose output during extraction
+/// * `options` - Options controlling how members are restored
 ///
 /// # Errors
 ///
@@ -23,40 +154,666 @@ use uucore::error::UResult;
 /// - The archive file cannot be opened
 /// - The archive format is invalid
 /// - Files cannot be extracted due to I/O or permission errors
-pub fn extract_archive(archive_path: &Path, verbose: bool) -> UResult<()> {
+pub fn extract_archive(archive_path: &Path, options: &ExtractOptions) -> UResult<()> {
     // Open the archive file
     let file = File::open(archive_path).map_err(|e| TarError::from_io_error(e, archive_path))?;
 
     // Create Archive instance
-    let mut archive = Archive::new(file);
-    let mut out = BufWriter::new(io::stdout().lock());
+    let volumes = Volumes::new(
+        archive_path,
+        options.blocking,
+        options.multi_volume.as_ref(),
+        "-x",
+        ArchiveFormat::Gnu,
+    )?;
+    let (tap, headers) = HeaderTap::new(volumes.reader(file));
+    let mut archive = Archive::new(tap);
+    archive.set_ignore_zeros(options.ignore_zeros);
+    // With `-O` the listing goes to standard error, out of the data's way.
+    let listing: Box<dyn Write> = if options.to_stdout {
+        Box::new(io::stderr())
+    } else {
+        Box::new(io::stdout().lock())
+    };
+    let mut out = listing_output(options.index_file.as_deref(), listing)?;
+    let mut confirmation = options.interactive.then(Confirmation::open);
+    let verbose = options.verbose > 0;
+    let mut listing = (options.verbose > 1).then(|| LongListing::new(options.listing));
 
     // Extract to current directory
     if verbose {
         writeln!(out, "Extracting archive: {}", archive_path.display()).map_err(TarError::Io)?;
     }
 
+    // As in GNU tar, `-O` wins over `--to-command`.
+    let to_command = options
+        .to_command
+        .as_ref()
+        .filter(|_| !options.to_stdout)
+        .map(|command| ToCommand {
+            command: command.clone(),
+            ignore_errors: options.ignore_command_error,
+            start_time: FileTime::now(),
+        });
+    let to_pipe = options.to_stdout || to_command.is_some();
+    let mut names = SafeNames::new(options.absolute_names);
+    let mut delayed_directories = Vec::new();
+    let mut delayed_links = Vec::new();
+
     // Iterate through entries for verbose output and error handling
-    for entry_result in archive.entries().map_err(TarError::CannotReadEntries)? {
-        let mut entry = entry_result.map_err(TarError::CannotReadEntry)?;
+    let entries = archive
+        .entries()
+        .map_err(|e| volumes.explain(TarError::CannotReadEntries(e)))?;
+    for (index, entry_result) in entries.enumerate() {
+        let mut entry = entry_result.map_err(|e| volumes.explain(TarError::CannotReadEntry(e)))?;
+        let block = entry.raw_header_position() / BLOCK_SIZE;
+
+        let label = volume::volume_label(&mut entry).map_err(TarError::CannotReadEntry)?;
+        if let Some(pattern) = options.label.as_deref().filter(|_| index == 0) {
+            volume::check_label(label.as_deref(), pattern, options.multi_volume.is_some())?;
+        }
 
-        // Get the path before unpacking (clone it so we can use it after borrowing entry mutably)
-        let path = entry
-            .path()
-            .map_err(TarError::CannotReadEntryPath)?
-            .to_path_buf();
+        // Global extended headers describe the archive, not a member, and
+        // volume labels are not extracted.
+        let header_type = entry.header().entry_type();
+        if header_type.is_pax_global_extensions() || header_type.as_byte() == GNU_VOLUME_LABEL {
+            if let Some(label) = label.filter(|_| verbose) {
+                write_block_number(&mut out, options.block_number, block).map_err(TarError::Io)?;
+                writeln!(out, "{label}").map_err(TarError::Io)?;
+            }
+            continue;
+        }
+
+        let mut member = headers.member(&entry).map_err(TarError::CannotReadEntry)?;
+        // Dumpdirs of incremental archives are directories listing what
+        // they should contain.
+        let mut dumpdir = None;
+        if member.entry_type.as_byte() == GNU_DUMPDIR {
+            let mut data = Vec::new();
+            entry
+                .read_to_end(&mut data)
+                .map_err(|e| volumes.explain(TarError::CannotReadEntry(e)))?;
+            dumpdir = Some(data);
+            member.entry_type = EntryType::Directory;
+        }
+        let path = member.path.clone();
+        let Some(destination) = names.member_name(&path) else {
+            continue;
+        };
+        if let Some(confirmation) = confirmation.as_mut() {
+            out.flush().map_err(TarError::Io)?;
+            if !confirmation
+                .ask("extract", &destination)
+                .map_err(TarError::Io)?
+            {
+                continue;
+            }
+        }
 
         if verbose {
+            write_block_number(&mut out, options.block_number, block).map_err(TarError::Io)?;
+        }
+        if let Some(listing) = listing.as_mut() {
+            let line = listing.member_line(&member, entry.header());
+            writeln!(out, "{line}").map_err(TarError::Io)?;
+        } else if verbose {
             writeln!(out, "{}", path.display()).map_err(TarError::Io)?;
         }
 
+        // The start of this member is on an earlier volume.
+        if member.entry_type.as_byte() == GNU_MULTIVOLUME {
+            uucore::show_error!(
+                "{}: Cannot extract -- file is continued from another volume",
+                path.display()
+            );
+            uucore::error::set_exit_code(2);
+            continue;
+        }
+
+        // Only the data of regular files is sent down a pipe.
+        if to_pipe {
+            if !member.entry_type.is_file() && !member.entry_type.is_contiguous() {
+                continue;
+            }
+            // The command shares our standard output.
+            out.flush().map_err(TarError::Io)?;
+            match pipe_member(
+                &mut entry,
+                &member,
+                &destination,
+                to_command.as_ref(),
+                &volumes,
+            )
+            .map_err(|e| volumes.explain(e))
+            {
+                Ok(()) => {}
+                Err(e @ (TarError::VolumeChange(_) | TarError::Io(_))) => return Err(e.into()),
+                Err(e) => {
+                    eprintln!("{e}");
+                    uucore::error::set_exit_code(2);
+                }
+            }
+            continue;
+        }
+
+        // Times are restored below, including for directories, which the
+        // tar crate never updates.
+        entry.set_preserve_mtime(false);
+        let times = member_times(&member, options);
+        let entry_type = member.entry_type;
+        let mode = member.mode & 0o777;
+        let member_xattrs = match &options.xattrs {
+            Some(filter) => xattrs::archived_xattrs(&member, filter),
+            None => Vec::new(),
+        };
+        let member_acls = if options.acls {
+            acls::archived_acls(&member)
+        } else {
+            ArchivedAcls::default()
+        };
+        let context = if options.selinux {
+            selinux::archived_context(&member)
+        } else {
+            None
+        };
+
         // Unpack the entry
-        entry.unpack_in(".").map_err(|e| TarError::CannotExtract {
+        let extract_error = |e| TarError::CannotExtract {
             path: path.clone(),
             source: e,
-        })?;
+        };
+        let sparse_map = sparse::archived_map(&member, &mut entry)
+            .map_err(|e| volumes.explain(extract_error(e)))?;
+        let backup = match back_up_existing(&destination, options) {
+            Ok(backup) => backup,
+            Err(e) => {
+                eprintln!("{e}");
+                uucore::error::set_exit_code(2);
+                continue;
+            }
+        };
+        if let Some(backup) = backup.as_ref().filter(|_| verbose) {
+            writeln!(
+                out,
+                "Renaming '{}' to '{}'",
+                destination.display(),
+                backup.display()
+            )
+            .map_err(TarError::Io)?;
+        }
+        let unpacked = prepare_destination(&destination, &member, options).and_then(|prepared| {
+            if !prepared {
+                return Ok(false);
+            }
+            match &sparse_map {
+                Some(map) => {
+                    unpack_sparse(&mut entry, &path, &destination, mode, map).map(|()| true)
+                }
+                None => unpack_entry(
+                    &mut entry,
+                    &member,
+                    &destination,
+                    &mut names,
+                    &mut delayed_links,
+                    times,
+                ),
+            }
+        });
+        // Like GNU tar, carry on with the next member when one cannot be
+        // written, unless the archive itself can no longer be read.
+        let unpacked = match unpacked.map_err(|e| volumes.explain(e)) {
+            Ok(unpacked) => unpacked,
+            Err(e @ TarError::VolumeChange(_)) => return Err(e.into()),
+            Err(e) => {
+                eprintln!("{e}");
+                uucore::error::set_exit_code(2);
+                // Put the old file back, as GNU tar does.
+                if let Some(backup) = backup {
+                    let _ = fs::rename(backup, &destination);
+                }
+                false
+            }
+        };
+
+        if !unpacked {
+            continue;
+        }
+
+        // Links share the attributes of their target, so only files and
+        // directories carry their own.
+        if !member_xattrs.is_empty() && !entry_type.is_symlink() && !entry_type.is_hard_link() {
+            xattrs::restore_xattrs(&destination, &member_xattrs);
+        }
+        // Unlike other attributes, symbolic links carry their own label.
+        if let Some(context) = context.filter(|_| !entry_type.is_hard_link()) {
+            selinux::restore_context(&destination, &context);
+        }
+
+        if entry_type.is_dir() {
+            // Keep the directory writable until its contents are in place.
+            set_mode(&destination, mode | 0o700);
+            if let Some(dumpdir) = dumpdir.filter(|_| options.incremental) {
+                remove_stale_entries(
+                    &destination,
+                    &dumpdir,
+                    verbose,
+                    &mut out,
+                    confirmation.as_mut(),
+                )?;
+            }
+            delayed_directories.push(DelayedDirectory {
+                path: destination,
+                mode,
+                times,
+                acls: member_acls,
+            });
+            continue;
+        }
+
+        if !member_acls.is_empty() && !entry_type.is_symlink() && !entry_type.is_hard_link() {
+            acls::restore_acls(&destination, &member_acls);
+        }
+        if let Some(times) = times {
+            if entry_type.is_symlink() {
+                set_times(&destination, times, true);
+            } else if !entry_type.is_hard_link() {
+                set_times(&destination, times, false);
+            }
+        }
     }
 
+    apply_delayed_links(delayed_links)?;
+    apply_delayed_directories(delayed_directories);
+
+    let mut reader = archive.into_inner().into_inner();
+    if options.block_number {
+        write_archive_end(&mut out, &reader, options.ignore_zeros).map_err(TarError::Io)?;
+    }
     out.flush().map_err(TarError::Io)?;
+    if !options.ignore_zeros {
+        reader.check_end().map_err(TarError::Io)?;
+    }
+    volumes.finish()?;
+    Ok(())
+}
+
+/// Sends the data of the regular file `member`, extracted as `name`, to
+/// `command` or else to standard output. Failing to write to standard
+/// output is fatal.
+fn pipe_member<R: Read>(
+    entry: &mut R,
+    member: &Member,
+    name: &Path,
+    command: Option<&ToCommand>,
+    volumes: &Volumes,
+) -> Result<(), TarError> {
+    let fail = |e| TarError::CannotExtract {
+        path: member.path.clone(),
+        source: e,
+    };
+    let sparse_map = sparse::archived_map(member, entry).map_err(fail)?;
+    let size = sparse_map.as_ref().map_or(member.size, |map| map.size);
+    let mut write = |mut out: &mut dyn Write| match &sparse_map {
+        Some(map) => sparse::expand(entry, &mut out, map),
+        None => io::copy(entry, out).map(drop),
+    };
+
+    match command {
+        Some(command) => command
+            .run(member, name, size, volumes, |stdin| write(stdin))
+            .map_err(fail),
+        None => {
+            let mut stdout = io::stdout().lock();
+            write(&mut stdout)
+                .and_then(|()| stdout.flush())
+                .map_err(TarError::Io)
+        }
+    }
+}
+
+/// Works out the times to restore on `member`, or `None` if the member
+/// should keep the time at which it was extracted.
+fn member_times(member: &Member, options: &ExtractOptions) -> Option<MemberTimes> {
+    if options.touch {
+        return None;
+    }
+
+    let atime = match member.atime {
+        Some(atime) if options.atime_preserve => atime,
+        _ => FileTime::now(),
+    };
+
+    Some(MemberTimes {
+        atime,
+        mtime: member.mtime,
+    })
+}
+
+/// Renames the file at `destination` out of the way when backups are
+/// requested, returning the name it was given. Like GNU tar, directories
+/// are left alone, and this happens before the overwrite options are
+/// looked at.
+fn back_up_existing(
+    destination: &Path,
+    options: &ExtractOptions,
+) -> Result<Option<PathBuf>, TarError> {
+    match fs::metadata(destination) {
+        Ok(existing) if !existing.is_dir() => {}
+        _ => return Ok(None),
+    }
+    let Some(backup) =
+        backup_control::get_backup_path(options.backup, destination, &options.backup_suffix)
+    else {
+        return Ok(None);
+    };
+    fs::rename(destination, &backup).map_err(|e| TarError::CannotBackup {
+        path: destination.to_path_buf(),
+        source: e,
+    })?;
+    Ok(Some(backup))
+}
+
+/// Deals with whatever is already at `destination` according to the
+/// overwrite options, returning `false` if `member` is to be skipped.
+fn prepare_destination(
+    destination: &Path,
+    member: &Member,
+    options: &ExtractOptions,
+) -> Result<bool, TarError> {
+    let Ok(existing) = fs::symlink_metadata(destination) else {
+        return Ok(true);
+    };
+    let is_dir = member.entry_type.is_dir();
+
+    if is_dir && existing.is_dir() {
+        // Directories are merged unless asked to be emptied first.
+        let keep = matches!(options.old_files, OldFiles::Keep | OldFiles::Skip);
+        if keep || !(options.recursive_unlink || options.old_files == OldFiles::UnlinkFirst) {
+            return Ok(true);
+        }
+    }
+    if is_dir
+        && existing.is_symlink()
+        && fs::metadata(destination).is_ok_and(|target| target.is_dir())
+        && (options.keep_directory_symlink
+            || matches!(options.old_files, OldFiles::Keep | OldFiles::Skip))
+    {
+        return Ok(false);
+    }
+
+    match options.old_files {
+        OldFiles::Keep if is_dir => Ok(false),
+        OldFiles::Keep => Err(TarError::FileExists {
+            path: destination.to_path_buf(),
+        }),
+        OldFiles::Skip => Ok(false),
+        OldFiles::KeepNewer
+            if !existing.is_dir()
+                && FileTime::from_last_modification_time(&existing) >= member.mtime =>
+        {
+            uucore::show_error!("Current '{}' is newer or same age", destination.display());
+            Ok(false)
+        }
+        OldFiles::UnlinkFirst => remove_existing(destination, &existing, options.recursive_unlink)
+            .map(|()| true)
+            .map_err(|e| TarError::CannotUnlink {
+                path: destination.to_path_buf(),
+                source: e,
+            }),
+        // Like GNU tar, a directory that is not empty is reported as being
+        // in the way rather than as failing to be removed.
+        OldFiles::Overwrite | OldFiles::KeepNewer => {
+            remove_existing(destination, &existing, options.recursive_unlink)
+                .map(|()| true)
+                .map_err(|e| match e.kind() {
+                    io::ErrorKind::DirectoryNotEmpty => TarError::FileExists {
+                        path: destination.to_path_buf(),
+                    },
+                    _ => TarError::CannotUnlink {
+                        path: destination.to_path_buf(),
+                        source: e,
+                    },
+                })
+        }
+    }
+}
+
+/// Deletes what the directory `path` holds beyond the entries its dumpdir
+/// lists, as they had been deleted by the time of the incremental dump.
+fn remove_stale_entries<W: Write, R: BufRead, C: Write>(
+    path: &Path,
+    dumpdir: &[u8],
+    verbose: bool,
+    out: &mut W,
+    mut confirmation: Option<&mut Confirmation<R, C>>,
+) -> Result<(), TarError> {
+    let stale = incremental::stale_entries(path, dumpdir).map_err(|e| TarError::CannotExtract {
+        path: path.to_path_buf(),
+        source: e,
+    })?;
+    for stale in stale {
+        if let Some(confirmation) = confirmation.as_mut() {
+            out.flush().map_err(TarError::Io)?;
+            if !confirmation.ask("delete", &stale).map_err(TarError::Io)? {
+                continue;
+            }
+        }
+        if verbose {
+            writeln!(
+                out,
+                "{}: Deleting '{}'",
+                uucore::util_name(),
+                stale.display()
+            )
+            .map_err(TarError::Io)?;
+        }
+        let removed = fs::symlink_metadata(&stale)
+            .and_then(|existing| remove_existing(&stale, &existing, true));
+        if let Err(e) = removed {
+            eprintln!(
+                "{}",
+                TarError::CannotRemove {
+                    path: stale,
+                    source: e
+                }
+            );
+            uucore::error::set_exit_code(2);
+        }
+    }
     Ok(())
 }
+
+/// Removes the file, link or directory described by `existing`. Directories
+/// that are not empty are only removed if `recursive`.
+fn remove_existing(path: &Path, existing: &fs::Metadata, recursive: bool) -> io::Result<()> {
+    if !existing.is_dir() {
+        fs::remove_file(path)
+    } else if recursive {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_dir(path)
+    }
+}
+
+/// Writes `entry` at `destination`, returning `false` if it is a symbolic
+/// link that has to wait until the end of the extraction.
+fn unpack_entry<R: Read>(
+    entry: &mut Entry<R>,
+    member: &Member,
+    destination: &Path,
+    names: &mut SafeNames,
+    delayed_links: &mut Vec<DelayedLink>,
+    times: Option<MemberTimes>,
+) -> Result<bool, TarError> {
+    let fail = |e| TarError::CannotExtract {
+        path: member.path.clone(),
+        source: e,
+    };
+
+    if let Some(parent) = destination.parent() {
+        fs::create_dir_all(parent).map_err(fail)?;
+    }
+
+    match &member.link_name {
+        Some(target) if member.entry_type.is_hard_link() => {
+            let target = names.hard_link_target(target);
+            fs::hard_link(&target, destination).map_err(|e| TarError::CannotHardLink {
+                path: member.path.clone(),
+                target,
+                source: e,
+            })?;
+        }
+        Some(target) if member.entry_type.is_symlink() && names.is_unsafe_symlink(target) => {
+            let placeholder = create_placeholder(destination).map_err(fail)?;
+            delayed_links.push(DelayedLink {
+                path: destination.to_path_buf(),
+                target: target.clone(),
+                placeholder,
+                times,
+            });
+            return Ok(false);
+        }
+        _ => {
+            entry.unpack(destination).map_err(fail)?;
+        }
+    }
+    Ok(true)
+}
+
+/// Writes a member stored in one of the PAX sparse formats, which the tar
+/// crate would extract as its condensed data.
+fn unpack_sparse<R: Read>(
+    data: &mut R,
+    path: &Path,
+    destination: &Path,
+    mode: u32,
+    map: &SparseMap,
+) -> Result<(), TarError> {
+    let fail = |e| TarError::CannotExtract {
+        path: path.to_path_buf(),
+        source: e,
+    };
+
+    if let Some(parent) = destination.parent() {
+        fs::create_dir_all(parent).map_err(fail)?;
+    }
+    sparse::extract(data, destination, map).map_err(fail)?;
+    set_mode(destination, mode);
+    Ok(())
+}
+
+/// Creates the empty file standing in for a delayed symbolic link,
+/// replacing whatever is at `path`.
+fn create_placeholder(path: &Path) -> io::Result<fs::Metadata> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    File::options()
+        .write(true)
+        .create_new(true)
+        .open(path)?
+        .metadata()
+}
+
+/// Replaces the placeholders of delayed symbolic links with the links
+/// themselves, unless a later member has taken their place.
+fn apply_delayed_links(links: Vec<DelayedLink>) -> Result<(), TarError> {
+    for link in links {
+        match fs::symlink_metadata(&link.path) {
+            Ok(metadata) if same_file(&metadata, &link.placeholder) => {}
+            _ => continue,
+        }
+        let fail = |e| TarError::CannotExtract {
+            path: link.path.clone(),
+            source: e,
+        };
+        fs::remove_file(&link.path).map_err(fail)?;
+        symlink(&link.target, &link.path).map_err(fail)?;
+        if let Some(times) = link.times {
+            set_times(&link.path, times, true);
+        }
+    }
+    Ok(())
+}
+
+/// Restores the mode and times of extracted directories, deepest first, so
+/// that restoring a parent never happens before its children are finished.
+fn apply_delayed_directories(mut directories: Vec<DelayedDirectory>) {
+    // The sort is stable, so a directory listed twice keeps its last metadata.
+    directories.sort_by_key(|dir| Reverse(dir.path.components().count()));
+
+    for dir in &directories {
+        set_mode(&dir.path, dir.mode);
+        // Setting the mode rewrites the ACL mask, so the ACL comes after it.
+        if !dir.acls.is_empty() {
+            acls::restore_acls(&dir.path, &dir.acls);
+        }
+        if let Some(times) = dir.times {
+            set_times(&dir.path, times, false);
+        }
+    }
+}
+
+/// Whether `a` and `b` describe the same file.
+#[cfg(unix)]
+fn same_file(a: &fs::Metadata, b: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    a.dev() == b.dev() && a.ino() == b.ino()
+}
+
+#[cfg(not(unix))]
+fn same_file(a: &fs::Metadata, b: &fs::Metadata) -> bool {
+    a.is_file() && b.is_file() && a.len() == b.len()
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, path: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, path)
+}
+
+#[cfg(not(unix))]
+fn symlink(_target: &Path, _path: &Path) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+/// Sets the permission bits of the extracted member at `path`.
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) {
+    use std::os::unix::fs::PermissionsExt;
+
+    let result = fs::set_permissions(path, fs::Permissions::from_mode(mode));
+    if let Err(e) = result {
+        report_attribute_error(TarError::CannotChangeMode {
+            path: path.to_path_buf(),
+            source: e,
+        });
+    }
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: u32) {}
+
+/// Applies `times` to the extracted member at `path`.
+fn set_times(path: &Path, times: MemberTimes, is_symlink: bool) {
+    let result = if is_symlink {
+        filetime::set_symlink_file_times(path, times.atime, times.mtime)
+    } else {
+        filetime::set_file_times(path, times.atime, times.mtime)
+    };
+    if let Err(e) = result {
+        report_attribute_error(TarError::CannotSetTimes {
+            path: path.to_path_buf(),
+            source: e,
+        });
+    }
+}
+
+/// Reports an attribute that could not be restored. As in GNU tar, the
+/// member stays extracted and the extraction carries on, but the run ends
+/// with a failure status.
+fn report_attribute_error(error: TarError) {
+    eprintln!("{error}");
+    uucore::error::set_exit_code(2);
+}
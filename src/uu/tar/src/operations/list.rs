@@ -3,86 +3,289 @@
 // For the full copyright and license information, please view the LICENSE
 // file that was distributed with this source code.
 
+use crate::confirm::Confirmation;
+use crate::date;
 use crate::errors::TarError;
-use chrono::{TimeZone, Utc};
+use crate::format::{
+    parse_numeric, ArchiveFormat, GNU_DUMPDIR, GNU_MULTIVOLUME, GNU_SPARSE, GNU_VOLUME_LABEL,
+};
+use crate::member::{HeaderTap, Member};
+use crate::volume::{self, Blocking, VolumeOptions, VolumeReader, Volumes, BLOCK_SIZE};
+use filetime::FileTime;
 use std::fs::File;
 use std::io::{self, BufWriter, Write};
-use std::path::Path;
-use tar::Archive;
+use std::path::{Path, PathBuf};
+use tar::{Archive, Header};
 use uucore::error::UResult;
 use uucore::fs::display_permissions_unix;
 
+/// Options controlling how an archive is listed
+#[derive(Debug, Clone, Default)]
+pub struct ListOptions {
+    /// Record size to read the archive in
+    pub blocking: Blocking,
+    /// Read an archive split over several volumes (`-M`)
+    pub multi_volume: Option<VolumeOptions>,
+    /// Glob the archive's volume label must match (`-V`)
+    pub label: Option<String>,
+    /// Read past zero blocks instead of stopping at the first (`-i`)
+    pub ignore_zeros: bool,
+    /// Ask before processing each member (`-w`)
+    pub interactive: bool,
+    /// How detailed the listing is: names only if 0, otherwise also
+    /// permissions, owners, sizes and dates (`-v`)
+    pub verbose: u8,
+    /// How a verbose listing shows owners and dates
+    pub listing: ListingFormat,
+    /// Start each line with the block the member's header is in (`-R`)
+    pub block_number: bool,
+    /// File to write the listing to instead of standard output
+    /// (`--index-file`)
+    pub index_file: Option<PathBuf>,
+}
+
+/// How a verbose listing shows owners and dates
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ListingFormat {
+    /// Show dates to the second, with any fraction (`--full-time`)
+    pub full_time: bool,
+    /// Show dates in UTC rather than local time (`--utc`)
+    pub utc: bool,
+    /// Show user and group ids rather than names (`--numeric-owner`)
+    pub numeric_owner: bool,
+}
+
 /// List the contents of a tar archive, printing one entry per line.
-pub fn list_archive(archive_path: &Path, verbose: bool) -> UResult<()> {
+pub fn list_archive(archive_path: &Path, options: &ListOptions) -> UResult<()> {
+    let verbose = options.verbose > 0;
     let file: File =
         File::open(archive_path).map_err(|e| TarError::from_io_error(e, archive_path))?;
-    let mut archive = Archive::new(file);
-    let mut out = BufWriter::new(io::stdout().lock());
+    let volumes = Volumes::new(
+        archive_path,
+        options.blocking,
+        options.multi_volume.as_ref(),
+        "-t",
+        ArchiveFormat::Gnu,
+    )?;
+    let (tap, headers) = HeaderTap::new(volumes.reader(file));
+    let mut archive = Archive::new(tap);
+    archive.set_ignore_zeros(options.ignore_zeros);
+    let mut out = listing_output(options.index_file.as_deref(), Box::new(io::stdout().lock()))?;
+    let mut confirmation = options.interactive.then(Confirmation::open);
+    let mut listing = LongListing::new(options.listing);
 
-    for entry_result in archive.entries().map_err(TarError::CannotReadEntries)? {
-        let entry = entry_result.map_err(TarError::CannotReadEntry)?;
+    let entries = archive
+        .entries()
+        .map_err(|e| volumes.explain(TarError::CannotReadEntries(e)))?;
+    for (index, entry_result) in entries.enumerate() {
+        let mut entry = entry_result.map_err(|e| volumes.explain(TarError::CannotReadEntry(e)))?;
+        let block = entry.raw_header_position() / BLOCK_SIZE;
 
-        if verbose {
-            // Collect all header fields into owned values before borrowing entry for the path,
-            // since both header() and path() require a borrow of entry.
-            let (mode, entry_type, owner, group, size, mtime) = {
-                let header = entry.header();
-                (
-                    header.mode().unwrap_or(0),
-                    header.entry_type(),
-                    header
-                        .username()
-                        .ok()
-                        .flatten()
-                        .unwrap_or_default()
-                        .to_owned(),
-                    header
-                        .groupname()
-                        .ok()
-                        .flatten()
-                        .unwrap_or_default()
-                        .to_owned(),
-                    header.size().unwrap_or(0),
-                    header.mtime().unwrap_or(0),
-                )
-            };
-
-            let path = entry.path().map_err(TarError::CannotReadEntryPath)?;
-
-            let type_char = match entry_type {
-                tar::EntryType::Directory => 'd',
-                tar::EntryType::Symlink => 'l',
-                tar::EntryType::Char => 'c',
-                tar::EntryType::Block => 'b',
-                tar::EntryType::Fifo => 'p',
-                _ => '-',
-            };
-            // Tar headers store the type separately from the mode bits, so we get the
-            // 9-character rwx string from uucore and prepend our own type character.
-            let perm_str = display_permissions_unix(mode, false);
-            let permissions = format!("{type_char}{perm_str}");
-
-            // TODO: GNU tar displays mtime in the user's local timezone; we
-            // currently format in UTC. Convert to local time for compatibility.
-            let dt: chrono::DateTime<Utc> = Utc
-                .timestamp_opt(mtime as i64, 0)
-                .single()
-                .unwrap_or_else(Utc::now);
-            let date_str = dt.format("%Y-%m-%d %H:%M");
-
-            writeln!(
-                out,
-                "{permissions} {owner}/{group} {size:>8} {date_str} {}",
-                path.display()
-            )
+        let label = volume::volume_label(&mut entry).map_err(TarError::CannotReadEntry)?;
+        if let Some(pattern) = options.label.as_deref().filter(|_| index == 0) {
+            volume::check_label(label.as_deref(), pattern, options.multi_volume.is_some())?;
+        }
+
+        // Global extended headers describe the archive, not a member, but
+        // POSIX archives keep the volume label there.
+        if entry.header().entry_type().is_pax_global_extensions() {
+            if label.is_some() {
+                write_block_number(&mut out, options.block_number, block).map_err(TarError::Io)?;
+            }
+            match label {
+                Some(label) if verbose => {
+                    let mtime = entry.header().mtime().unwrap_or_default();
+                    let line = listing.line(
+                        "V---------",
+                        "0",
+                        "0",
+                        "0",
+                        FileTime::from_unix_time(mtime as i64, 0),
+                        &format!("{label}--Volume Header--"),
+                    );
+                    writeln!(out, "{line}")
+                }
+                Some(label) => writeln!(out, "{label}"),
+                None => Ok(()),
+            }
             .map_err(TarError::Io)?;
-        } else {
-            let path = entry.path().map_err(TarError::CannotReadEntryPath)?;
+            continue;
+        }
+
+        let member = headers.member(&entry).map_err(TarError::CannotReadEntry)?;
+        if let Some(confirmation) = confirmation.as_mut() {
+            out.flush().map_err(TarError::Io)?;
+            if !confirmation
+                .ask("list", &member.path)
+                .map_err(TarError::Io)?
+            {
+                continue;
+            }
+        }
 
-            writeln!(out, "{}", path.display()).map_err(TarError::Io)?;
+        write_block_number(&mut out, options.block_number, block).map_err(TarError::Io)?;
+        if verbose {
+            let line = listing.member_line(&member, entry.header());
+            writeln!(out, "{line}").map_err(TarError::Io)?;
+        } else {
+            writeln!(out, "{}", member.path.display()).map_err(TarError::Io)?;
         }
     }
 
+    let mut reader = archive.into_inner().into_inner();
+    if options.block_number {
+        write_archive_end(&mut out, &reader, options.ignore_zeros).map_err(TarError::Io)?;
+    }
     out.flush().map_err(TarError::Io)?;
+    if !options.ignore_zeros {
+        reader.check_end().map_err(TarError::Io)?;
+    }
+    volumes.finish()?;
     Ok(())
 }
+
+/// Opens where verbose output goes: the `--index-file` if one is given,
+/// otherwise `default`.
+pub fn listing_output(
+    index_file: Option<&Path>,
+    default: Box<dyn Write>,
+) -> Result<BufWriter<Box<dyn Write>>, TarError> {
+    let output: Box<dyn Write> = match index_file {
+        Some(path) => Box::new(File::create(path).map_err(|e| TarError::from_io_error(e, path))?),
+        None => default,
+    };
+    Ok(BufWriter::new(output))
+}
+
+/// Starts a line about the member whose header is at `block` with the
+/// block number, when asked to (`-R`).
+pub fn write_block_number<W: Write>(out: &mut W, enabled: bool, block: u64) -> io::Result<()> {
+    if enabled {
+        write!(out, "block {block}: ")?;
+    }
+    Ok(())
+}
+
+/// Reports where the archive ended (`-R`): at the zero block reading
+/// stopped at or, when reading past zero blocks, at the end of the file.
+pub fn write_archive_end<W: Write>(
+    out: &mut W,
+    reader: &VolumeReader,
+    ignore_zeros: bool,
+) -> io::Result<()> {
+    if ignore_zeros {
+        writeln!(out, "block {}: ** End of File **", reader.blocks_read())
+    } else if let Some(block) = reader.zero_block() {
+        writeln!(out, "block {block}: ** Block of NULs **")
+    } else {
+        Ok(())
+    }
+}
+
+/// Builds the lines of a verbose listing the way GNU tar's `tar -tv` does.
+///
+/// The owner, group and size share a column that starts out 19 characters
+/// wide and, like the date column, widens for good once an entry needs
+/// more room, so that the following lines stay aligned with it.
+#[derive(Debug)]
+pub struct LongListing {
+    format: ListingFormat,
+    owner_size_width: usize,
+    date_width: usize,
+}
+
+impl LongListing {
+    pub fn new(format: ListingFormat) -> Self {
+        Self {
+            format,
+            owner_size_width: 19,
+            date_width: 16,
+        }
+    }
+
+    /// Describes `member`, whose last header is `header`.
+    pub fn member_line(&mut self, member: &Member, header: &Header) -> String {
+        let type_char = type_char(member);
+        // Tar headers store the type separately from the mode bits, so we get the
+        // 9-character rwx string from uucore and prepend our own type character.
+        let modes = format!(
+            "{type_char}{}",
+            display_permissions_unix(member.mode, false)
+        );
+
+        // Like GNU tar, fall back to the numeric ids when no names are recorded.
+        let (owner, group) = if self.format.numeric_owner {
+            (None, None)
+        } else {
+            (member.uname.clone(), member.gname.clone())
+        };
+        let owner = owner.unwrap_or_else(|| member.uid.to_string());
+        let group = group.unwrap_or_else(|| member.gid.to_string());
+        let size = match (header.device_major(), header.device_minor()) {
+            (Ok(Some(major)), Ok(Some(minor))) if matches!(type_char, 'b' | 'c') => {
+                format!("{major},{minor}")
+            }
+            _ => member.size.to_string(),
+        };
+
+        let mut name = member.path.display().to_string();
+        match (type_char, &member.link_name) {
+            ('l', Some(target)) => name.push_str(&format!(" -> {}", target.display())),
+            ('h', Some(target)) => name.push_str(&format!(" link to {}", target.display())),
+            // A volume that starts partway through a member says where its
+            // part of the data belongs.
+            ('M', _) => {
+                let offset = parse_numeric(&header.as_bytes()[369..381]);
+                name.push_str(&format!("--Continued at byte {offset}--"));
+            }
+            ('V', _) => name.push_str("--Volume Header--"),
+            _ => {}
+        }
+
+        self.line(&modes, &owner, &group, &size, member.mtime, &name)
+    }
+
+    /// Lays out the fields of one line, `name` including anything shown
+    /// after it.
+    pub fn line(
+        &mut self,
+        modes: &str,
+        owner: &str,
+        group: &str,
+        size: &str,
+        mtime: FileTime,
+        name: &str,
+    ) -> String {
+        let time = date::format_time(mtime, self.format.full_time, self.format.utc);
+        let pad = owner.len() + 1 + group.len() + 1 + size.len();
+        self.owner_size_width = self.owner_size_width.max(pad);
+        self.date_width = self.date_width.max(time.len());
+        format!(
+            "{modes} {owner}/{group} {size:>size_width$} {time:<date_width$} {name}",
+            size_width = self.owner_size_width - pad + size.len(),
+            date_width = self.date_width,
+        )
+    }
+}
+
+/// Returns the character standing for the type of `member` in a verbose
+/// listing.
+fn type_char(member: &Member) -> char {
+    match member.entry_type.as_byte() {
+        // Old archives mark directories with a trailing slash only.
+        b'0' | b'\0' | GNU_SPARSE if member.path.as_os_str().as_encoded_bytes().ends_with(b"/") => {
+            'd'
+        }
+        b'0' | b'\0' | GNU_SPARSE => '-',
+        b'1' => 'h',
+        b'2' => 'l',
+        b'3' => 'c',
+        b'4' => 'b',
+        b'5' | GNU_DUMPDIR => 'd',
+        b'6' => 'p',
+        b'7' => 'C',
+        GNU_MULTIVOLUME => 'M',
+        GNU_VOLUME_LABEL => 'V',
+        _ => '?',
+    }
+}
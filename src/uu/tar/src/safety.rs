@@ -0,0 +1,168 @@
+// This file is part of the uutils tar package.
+//
+// For the full copyright and license information, please view the LICENSE
+// file that was distributed with this source code.
+
+//! Keeping extracted members inside the directory tar runs in.
+//!
+//! Unless `-P` is given, GNU tar's rules apply. Leading `/`s, and
+//! everything up to the last `..` component, are removed from member names
+//! and hard link targets, with a warning the first time each prefix is
+//! seen. A member whose name contains `..` is not extracted at all.
+//! Symbolic links that could point outside, because their target is
+//! absolute or contains `..`, are created only once every member has been
+//! extracted, so a later member cannot be written through one.
+
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+
+/// Sanitizes the names read from an archive, reporting each removed prefix
+/// once like GNU tar
+#[derive(Debug)]
+pub struct SafeNames {
+    /// Extract names as they are (`-P`)
+    absolute_names: bool,
+    /// Warnings already shown
+    reported: HashSet<String>,
+}
+
+impl SafeNames {
+    pub fn new(absolute_names: bool) -> Self {
+        Self {
+            absolute_names,
+            reported: HashSet::new(),
+        }
+    }
+
+    /// Returns where to extract the member named `name`, or `None` if the
+    /// name contains `..` and the member must be skipped.
+    pub fn member_name(&mut self, name: &Path) -> Option<PathBuf> {
+        if self.absolute_names {
+            return Some(name.to_path_buf());
+        }
+        // A rejected member gets no warning about its prefix as well.
+        if name.components().any(|c| c == Component::ParentDir) {
+            uucore::show_error!("{}: Member name contains '..'", name.display());
+            uucore::error::set_exit_code(2);
+            return None;
+        }
+        Some(self.strip_prefix(name, "member names"))
+    }
+
+    /// Returns the file a hard link member should be linked to.
+    pub fn hard_link_target(&mut self, target: &Path) -> PathBuf {
+        if self.absolute_names {
+            return target.to_path_buf();
+        }
+        self.strip_prefix(target, "hard link targets")
+    }
+
+    /// Whether a symbolic link to `target` must wait until the end of the
+    /// extraction.
+    pub fn is_unsafe_symlink(&self, target: &Path) -> bool {
+        !self.absolute_names
+            && target.components().any(|c| {
+                matches!(
+                    c,
+                    Component::Prefix(_) | Component::RootDir | Component::ParentDir
+                )
+            })
+    }
+
+    /// Removes leading `/`s and everything up to the last `..` component
+    /// from `name`, warning about the prefix the first time it is seen.
+    fn strip_prefix(&mut self, name: &Path, kind: &str) -> PathBuf {
+        let bytes = name.as_os_str().as_encoded_bytes();
+        let prefix = &bytes[..unsafe_prefix_len(bytes)];
+        if !prefix.is_empty() {
+            let message = format!(
+                "Removing leading `{}' from {kind}",
+                String::from_utf8_lossy(prefix)
+            );
+            if self.reported.insert(message.clone()) {
+                uucore::show_error!("{message}");
+            }
+        }
+
+        let components: Vec<Component> = name.components().collect();
+        let skip = components
+            .iter()
+            .rposition(|c| {
+                matches!(
+                    c,
+                    Component::Prefix(_) | Component::RootDir | Component::ParentDir
+                )
+            })
+            .map_or(0, |last| last + 1);
+        let safe: PathBuf = components[skip..].iter().collect();
+        if safe.as_os_str().is_empty() {
+            PathBuf::from(".")
+        } else {
+            safe
+        }
+    }
+}
+
+/// Length of the part of `name` that GNU tar's `safer_name_suffix` drops:
+/// everything up to the end of the last `..` component, then any slashes.
+fn unsafe_prefix_len(name: &[u8]) -> usize {
+    let mut len = 0;
+    let mut start = 0;
+    while start < name.len() {
+        let rest = &name[start..];
+        if rest.starts_with(b"..") && matches!(rest.get(2), None | Some(b'/')) {
+            len = start + 2;
+        }
+        match rest.iter().position(|&b| b == b'/') {
+            Some(slash) => start += slash + 1,
+            None => break,
+        }
+    }
+    len + name[len..].iter().take_while(|&&b| b == b'/').count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unsafe_prefix_len() {
+        assert_eq!(unsafe_prefix_len(b"a/b"), 0);
+        assert_eq!(unsafe_prefix_len(b"/etc/passwd"), 1);
+        assert_eq!(unsafe_prefix_len(b"//double"), 2);
+        assert_eq!(unsafe_prefix_len(b"../../escape"), 6);
+        assert_eq!(unsafe_prefix_len(b"a/../b"), 5);
+        assert_eq!(unsafe_prefix_len(b"c/.."), 4);
+        assert_eq!(unsafe_prefix_len(b"a/..b/c"), 0);
+        assert_eq!(unsafe_prefix_len(b"/x/..//y"), 7);
+    }
+
+    #[test]
+    fn test_member_name() {
+        let mut names = SafeNames::new(false);
+        assert_eq!(
+            names.member_name(Path::new("/etc/passwd")),
+            Some(PathBuf::from("etc/passwd"))
+        );
+        assert_eq!(names.member_name(Path::new("/")), Some(PathBuf::from(".")));
+        assert_eq!(names.member_name(Path::new("a/../b")), None);
+        assert_eq!(
+            names.hard_link_target(Path::new("../x")),
+            PathBuf::from("x")
+        );
+        assert!(names.is_unsafe_symlink(Path::new("/etc")));
+        assert!(names.is_unsafe_symlink(Path::new("d/../../x")));
+        assert!(!names.is_unsafe_symlink(Path::new("d/x")));
+
+        let mut names = SafeNames::new(true);
+        assert_eq!(
+            names.member_name(Path::new("/etc/passwd")),
+            Some(PathBuf::from("/etc/passwd"))
+        );
+        assert_eq!(
+            names.member_name(Path::new("../x")),
+            Some(PathBuf::from("../x"))
+        );
+        assert!(!names.is_unsafe_symlink(Path::new("/etc")));
+    }
+}
@@ -0,0 +1,97 @@
+// This file is part of the uutils tar package.
+//
+// For the full copyright and license information, please view the LICENSE
+// file that was distributed with this source code.
+
+//! SELinux context support (`--selinux`).
+//!
+//! Contexts are stored in PAX extended headers as `RHT.security.selinux`
+//! records, the keyword used by Red Hat's GNU tar, and are read and written
+//! through the `security.selinux` extended attribute.
+
+use crate::member::Member;
+use std::io;
+use std::path::Path;
+
+/// PAX keyword holding the SELinux context of a member
+pub const SELINUX_PAX_KEY: &str = "RHT.security.selinux";
+
+/// Extended attribute in which the kernel keeps a file's context
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const SELINUX_XATTR: &str = "security.selinux";
+
+/// Reads the SELinux context of `path` as a PAX record.
+///
+/// Files without a label, and systems without SELinux, yield no record.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn read_pax_record(path: &Path) -> io::Result<Option<(String, Vec<u8>)>> {
+    let value = match xattr::get_deref(path, SELINUX_XATTR) {
+        Ok(value) => value,
+        Err(e) if e.kind() == io::ErrorKind::Unsupported => None,
+        Err(e) => return Err(e),
+    };
+    Ok(value
+        .and_then(|value| encode_context(&value))
+        .map(|context| (SELINUX_PAX_KEY.to_string(), context)))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub fn read_pax_record(_path: &Path) -> io::Result<Option<(String, Vec<u8>)>> {
+    Ok(None)
+}
+
+/// Returns the context recorded for `member`, if any.
+pub fn archived_context(member: &Member) -> Option<Vec<u8>> {
+    member.record(SELINUX_PAX_KEY).and_then(encode_context)
+}
+
+/// Labels the extracted member at `path` with `context`.
+///
+/// Symbolic links are labelled themselves rather than their target. As
+/// with ACLs, failures are reported as warnings since they usually mean
+/// SELinux is disabled or the policy forbids the label.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn restore_context(path: &Path, context: &[u8]) {
+    if let Err(e) = xattr::set(path, SELINUX_XATTR, &decode_context(context)) {
+        uucore::show_warning!("{}: Cannot set SELinux context: {e}", path.display());
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub fn restore_context(_path: &Path, _context: &[u8]) {}
+
+/// Turns an attribute value into the text stored in the archive. The
+/// kernel keeps the terminating NUL that `setfilecon` writes; the archive
+/// does not.
+fn encode_context(value: &[u8]) -> Option<Vec<u8>> {
+    let end = value.iter().rposition(|&b| b != 0)? + 1;
+    Some(value[..end].to_vec())
+}
+
+/// Turns an archived context back into an attribute value, NUL-terminated
+/// as `setfilecon` would write it.
+#[cfg_attr(not(any(target_os = "linux", target_os = "android")), allow(dead_code))]
+fn decode_context(context: &[u8]) -> Vec<u8> {
+    let mut value = context.to_vec();
+    value.push(0);
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context_roundtrip() {
+        let value = b"system_u:object_r:user_home_t:s0\0";
+        let context = encode_context(value).unwrap();
+        assert_eq!(context, b"system_u:object_r:user_home_t:s0");
+        assert_eq!(decode_context(&context), value);
+    }
+
+    #[test]
+    fn test_empty_context_is_not_recorded() {
+        assert_eq!(encode_context(b""), None);
+        assert_eq!(encode_context(b"\0"), None);
+    }
+}
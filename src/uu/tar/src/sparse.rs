@@ -0,0 +1,541 @@
+// This file is part of the uutils tar package.
+//
+// For the full copyright and license information, please view the LICENSE
+// file that was distributed with this source code.
+
+//! Sparse file support (`-S`, `--sparse-version`).
+//!
+//! Only the data regions of a sparse file are stored, together with a map
+//! of where they belong. GNU archives use the old `S` header type, with the
+//! map in the header and extension blocks after it. POSIX archives use one
+//! of GNU's PAX encodings:
+//!
+//! * 0.0: repeated `GNU.sparse.offset`/`GNU.sparse.numbytes` records
+//! * 0.1: a single `GNU.sparse.map` record
+//! * 1.0: the map at the start of the member data, and the real name in
+//!   `GNU.sparse.name` so that other tars extract the condensed data under
+//!   a different name
+
+use crate::member::Member;
+use crate::pax::{self, PaxRecord};
+use std::fmt;
+use std::fs::{self, File, Metadata};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use tar::{EntryType, GnuExtSparseHeader, Header};
+
+const BLOCK_SIZE: u64 = 512;
+
+/// Name template for the ustar header of a 1.0 sparse member
+pub const SPARSE_NAME_TEMPLATE: &str = "%d/GNUSparseFile.%p/%f";
+
+/// PAX encoding used for sparse members of POSIX archives
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SparseVersion {
+    V0_0,
+    V0_1,
+    #[default]
+    V1_0,
+}
+
+impl SparseVersion {
+    /// Parses a `--sparse-version` value, `MAJOR[.MINOR]`.
+    pub fn parse(value: &str) -> Option<Self> {
+        let (major, minor) = value.split_once('.').unwrap_or((value, "0"));
+        match (major.parse::<u32>().ok()?, minor.parse::<u32>().ok()?) {
+            (0, 0) => Some(Self::V0_0),
+            (0, 1) => Some(Self::V0_1),
+            (1, 0) => Some(Self::V1_0),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SparseVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::V0_0 => "0.0",
+            Self::V0_1 => "0.1",
+            Self::V1_0 => "1.0",
+        })
+    }
+}
+
+/// Where the data of a sparse file lies
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SparseMap {
+    /// Offset and length of each data region, in order. A file ending in a
+    /// hole has a final empty region at its end.
+    pub regions: Vec<(u64, u64)>,
+    /// Size of the whole file, holes included
+    pub size: u64,
+}
+
+impl SparseMap {
+    /// Number of bytes of data the archive holds for the file.
+    pub fn stored_size(&self) -> u64 {
+        self.regions.iter().map(|&(_, length)| length).sum()
+    }
+}
+
+/// Finds the data regions of `file`, or returns `None` if it has no holes
+/// and should be stored as a regular file.
+pub fn scan(file: &mut File, meta: &Metadata) -> io::Result<Option<SparseMap>> {
+    let size = meta.len();
+    if size == 0 || !may_have_holes(meta) {
+        return Ok(None);
+    }
+
+    let regions = match seek_regions(file, size)? {
+        Some(regions) => regions,
+        None => zero_block_regions(file, size)?,
+    };
+    let regions = align_regions(&regions, size);
+    if regions == [(0, size)] {
+        return Ok(None);
+    }
+    Ok(Some(SparseMap { regions, size }))
+}
+
+/// Whether fewer blocks are allocated than the size needs. Files without
+/// holes skip the scan.
+#[cfg(unix)]
+fn may_have_holes(meta: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    meta.blocks() * BLOCK_SIZE < meta.len()
+}
+
+#[cfg(not(unix))]
+fn may_have_holes(_meta: &Metadata) -> bool {
+    true
+}
+
+/// Asks the filesystem where the data is with `SEEK_DATA`/`SEEK_HOLE`.
+/// Returns `None` if the filesystem cannot tell.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "freebsd"
+))]
+fn seek_regions(file: &File, size: u64) -> io::Result<Option<Vec<(u64, u64)>>> {
+    use std::os::fd::AsRawFd;
+
+    let fd = file.as_raw_fd();
+    let mut regions = Vec::new();
+    let mut position = 0;
+    while position < size {
+        // SAFETY: lseek only moves the offset of a descriptor `file` owns.
+        let data = unsafe { libc::lseek(fd, position as libc::off_t, libc::SEEK_DATA) };
+        if data < 0 {
+            let e = io::Error::last_os_error();
+            return match e.raw_os_error() {
+                // No data past `position`: the rest is a hole.
+                Some(libc::ENXIO) => Ok(Some(regions)),
+                Some(libc::EINVAL) => Ok(None),
+                _ => Err(e),
+            };
+        }
+        // SAFETY: as above.
+        let hole = unsafe { libc::lseek(fd, data, libc::SEEK_HOLE) };
+        if hole < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let (data, hole) = (data as u64, (hole as u64).min(size));
+        if hole > data {
+            regions.push((data, hole - data));
+        }
+        position = hole;
+    }
+    Ok(Some(regions))
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "freebsd"
+)))]
+fn seek_regions(_file: &File, _size: u64) -> io::Result<Option<Vec<(u64, u64)>>> {
+    Ok(None)
+}
+
+/// Finds the data by reading the file and treating blocks of zeros as
+/// holes, like GNU tar does when the filesystem cannot report them.
+fn zero_block_regions(file: &mut File, size: u64) -> io::Result<Vec<(u64, u64)>> {
+    file.seek(SeekFrom::Start(0))?;
+    let mut reader = BufReader::new(file);
+    let mut block = [0u8; BLOCK_SIZE as usize];
+    let mut regions: Vec<(u64, u64)> = Vec::new();
+    let mut offset = 0;
+    while offset < size {
+        let len = (size - offset).min(BLOCK_SIZE) as usize;
+        reader.read_exact(&mut block[..len])?;
+        if block[..len].iter().any(|&b| b != 0) {
+            match regions.last_mut() {
+                Some((start, length)) if *start + *length == offset => *length += len as u64,
+                _ => regions.push((offset, len as u64)),
+            }
+        }
+        offset += len as u64;
+    }
+    Ok(regions)
+}
+
+/// Widens `regions` to whole archive blocks, merging any that then touch,
+/// and marks a trailing hole with an empty region. Readers such as the tar
+/// crate require every region but the last to fill whole blocks.
+fn align_regions(regions: &[(u64, u64)], size: u64) -> Vec<(u64, u64)> {
+    let mut aligned: Vec<(u64, u64)> = Vec::new();
+    for &(offset, length) in regions {
+        let start = offset / BLOCK_SIZE * BLOCK_SIZE;
+        let end = (offset + length).div_ceil(BLOCK_SIZE) * BLOCK_SIZE;
+        let end = end.min(size);
+        match aligned.last_mut() {
+            Some((last, last_length)) if *last + *last_length >= start => {
+                *last_length = end.max(*last + *last_length) - *last;
+            }
+            _ => aligned.push((start, end - start)),
+        }
+    }
+    if aligned
+        .last()
+        .is_none_or(|&(offset, length)| offset + length < size)
+    {
+        aligned.push((size, 0));
+    }
+    aligned
+}
+
+/// Reads the data regions of a sparse file back to back.
+pub struct RegionReader<R> {
+    inner: R,
+    regions: std::vec::IntoIter<(u64, u64)>,
+    remaining: u64,
+}
+
+impl<R: Read + Seek> RegionReader<R> {
+    pub fn new(inner: R, map: &SparseMap) -> Self {
+        Self {
+            inner,
+            regions: map.regions.clone().into_iter(),
+            remaining: 0,
+        }
+    }
+}
+
+impl<R: Read + Seek> Read for RegionReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.remaining == 0 {
+            let Some((offset, length)) = self.regions.next() else {
+                return Ok(0);
+            };
+            self.inner.seek(SeekFrom::Start(offset))?;
+            self.remaining = length;
+        }
+
+        let len = buf
+            .len()
+            .min(usize::try_from(self.remaining).unwrap_or(usize::MAX));
+        let n = self.inner.read(&mut buf[..len])?;
+        if n == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "file shrank while being read",
+            ));
+        }
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+/// Turns `header` into a GNU sparse (`S`) header for `map` and returns the
+/// extension blocks that must follow it, holding the regions that do not
+/// fit in the header.
+pub fn set_gnu_header(header: &mut Header, map: &SparseMap) -> Vec<u8> {
+    header.set_entry_type(EntryType::GNUSparse);
+    header.set_size(map.stored_size());
+
+    let (first, rest) = map.regions.split_at(map.regions.len().min(4));
+    let Some(gnu) = header.as_gnu_mut() else {
+        return Vec::new();
+    };
+    gnu.set_real_size(map.size);
+    gnu.set_is_extended(!rest.is_empty());
+    for (entry, &(offset, length)) in gnu.sparse.iter_mut().zip(first) {
+        entry.set_offset(offset);
+        entry.set_length(length);
+    }
+
+    let mut blocks = Vec::new();
+    let chunks: Vec<_> = rest.chunks(21).collect();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let mut ext = GnuExtSparseHeader::new();
+        for (entry, &(offset, length)) in ext.sparse_mut().iter_mut().zip(*chunk) {
+            entry.set_offset(offset);
+            entry.set_length(length);
+        }
+        ext.set_is_extended(i + 1 < chunks.len());
+        blocks.extend_from_slice(ext.as_bytes());
+    }
+    blocks
+}
+
+/// PAX records describing `map` for the member `name` in `version`.
+pub fn pax_records(map: &SparseMap, version: SparseVersion, name: &Path) -> Vec<PaxRecord> {
+    let record = |key: &str, value: String| (key.to_string(), value.into_bytes());
+    match version {
+        SparseVersion::V0_0 | SparseVersion::V0_1 => {
+            let mut records = vec![
+                record("GNU.sparse.size", map.size.to_string()),
+                record("GNU.sparse.numblocks", map.regions.len().to_string()),
+            ];
+            if version == SparseVersion::V0_0 {
+                for &(offset, length) in &map.regions {
+                    records.push(record("GNU.sparse.offset", offset.to_string()));
+                    records.push(record("GNU.sparse.numbytes", length.to_string()));
+                }
+            } else {
+                let numbers: Vec<String> = map
+                    .regions
+                    .iter()
+                    .flat_map(|&(offset, length)| [offset.to_string(), length.to_string()])
+                    .collect();
+                records.push(record("GNU.sparse.map", numbers.join(",")));
+            }
+            records
+        }
+        SparseVersion::V1_0 => vec![
+            record("GNU.sparse.major", "1".to_string()),
+            record("GNU.sparse.minor", "0".to_string()),
+            record("GNU.sparse.name", name.to_string_lossy().into_owned()),
+            record("GNU.sparse.realsize", map.size.to_string()),
+        ],
+    }
+}
+
+/// Name under which a 1.0 sparse member is stored in its ustar header.
+pub fn member_name(name: &Path, reproducible: bool) -> String {
+    pax::expand_name(SPARSE_NAME_TEMPLATE, Some(name), 0, reproducible)
+}
+
+/// The map that precedes the data of a 1.0 sparse member: the number of
+/// regions, then each offset and length, one decimal number per line,
+/// padded to a whole block.
+pub fn map_block(map: &SparseMap) -> Vec<u8> {
+    let mut data = format!("{}\n", map.regions.len()).into_bytes();
+    for &(offset, length) in &map.regions {
+        data.extend_from_slice(format!("{offset}\n{length}\n").as_bytes());
+    }
+    data.resize(
+        data.len().div_ceil(BLOCK_SIZE as usize) * BLOCK_SIZE as usize,
+        0,
+    );
+    data
+}
+
+/// Returns the map of a member stored in one of the PAX sparse formats, or
+/// `None` if it is not one. For format 1.0 the map is read from the start
+/// of `data`, leaving it positioned at the file data.
+pub fn archived_map<R: Read>(member: &Member, data: &mut R) -> io::Result<Option<SparseMap>> {
+    let number = |key| -> io::Result<Option<u64>> {
+        member
+            .record(key)
+            .map(|value| parse_number(value).ok_or_else(malformed))
+            .transpose()
+    };
+
+    let (regions, size) = if member.record("GNU.sparse.major") == Some(b"1") {
+        (read_map_block(data)?, number("GNU.sparse.realsize")?)
+    } else if let Some(value) = member.record("GNU.sparse.map") {
+        let numbers = value
+            .split(|&b| b == b',')
+            .filter(|number| !number.is_empty())
+            .map(|number| parse_number(number).ok_or_else(malformed))
+            .collect::<io::Result<Vec<_>>>()?;
+        if numbers.len() % 2 != 0 {
+            return Err(malformed());
+        }
+        let regions = numbers.chunks(2).map(|pair| (pair[0], pair[1])).collect();
+        (regions, number("GNU.sparse.size")?)
+    } else if member.record("GNU.sparse.offset").is_some() {
+        let values = |key: &str| -> io::Result<Vec<u64>> {
+            member
+                .records
+                .iter()
+                .filter(|(keyword, _)| keyword == key)
+                .map(|(_, value)| parse_number(value).ok_or_else(malformed))
+                .collect()
+        };
+        let offsets = values("GNU.sparse.offset")?;
+        let lengths = values("GNU.sparse.numbytes")?;
+        if offsets.len() != lengths.len() {
+            return Err(malformed());
+        }
+        let regions = offsets.into_iter().zip(lengths).collect();
+        (regions, number("GNU.sparse.size")?)
+    } else {
+        return Ok(None);
+    };
+
+    let end = regions
+        .last()
+        .map_or(0, |&(offset, length)| offset + length);
+    Ok(Some(SparseMap {
+        regions,
+        size: size.unwrap_or(end),
+    }))
+}
+
+/// Reads the map block at the start of a 1.0 sparse member's data.
+fn read_map_block<R: Read>(data: &mut R) -> io::Result<Vec<(u64, u64)>> {
+    let mut text = Vec::new();
+    let mut block = [0u8; BLOCK_SIZE as usize];
+    loop {
+        data.read_exact(&mut block)?;
+        text.extend_from_slice(&block);
+
+        // Only lines ended by a newline are complete; a number may be cut
+        // off at the block boundary.
+        let end = text.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+        let numbers = text[..end]
+            .split(|&b| b == b'\n')
+            .take_while(|line| !line.is_empty())
+            .map(|line| parse_number(line).ok_or_else(malformed))
+            .collect::<io::Result<Vec<u64>>>()?;
+        let Some((&count, pairs)) = numbers.split_first() else {
+            continue;
+        };
+
+        let wanted = count.saturating_mul(2);
+        if (pairs.len() as u64) < wanted {
+            // Each number takes at most 21 bytes with its newline.
+            if text.len() as u64 > (wanted + 1) * 21 + BLOCK_SIZE {
+                return Err(malformed());
+            }
+            continue;
+        }
+        return Ok(pairs[..wanted as usize]
+            .chunks(2)
+            .map(|pair| (pair[0], pair[1]))
+            .collect());
+    }
+}
+
+/// Writes a sparse member's `data` to a new file at `path`, leaving holes
+/// where the map has no data.
+pub fn extract<R: Read>(data: &mut R, path: &Path, map: &SparseMap) -> io::Result<()> {
+    // Replace rather than write through whatever is already there.
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    let mut file = File::create(path)?;
+    for &(offset, length) in &map.regions {
+        file.seek(SeekFrom::Start(offset))?;
+        let copied = io::copy(&mut data.take(length), &mut file)?;
+        if copied != length {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "sparse member data ends early",
+            ));
+        }
+    }
+    file.set_len(map.size)?;
+    file.flush()
+}
+
+/// Writes a sparse member's `data` to `out` as the whole file, with its
+/// holes filled with zeros, for `-O` and `--to-command`.
+pub fn expand<R: Read, W: Write>(data: &mut R, out: &mut W, map: &SparseMap) -> io::Result<()> {
+    let mut position = 0;
+    for &(offset, length) in &map.regions {
+        io::copy(
+            &mut io::repeat(0).take(offset.saturating_sub(position)),
+            out,
+        )?;
+        let copied = io::copy(&mut data.take(length), out)?;
+        if copied != length {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "sparse member data ends early",
+            ));
+        }
+        position = offset + length;
+    }
+    io::copy(
+        &mut io::repeat(0).take(map.size.saturating_sub(position)),
+        out,
+    )?;
+    Ok(())
+}
+
+fn parse_number(value: &[u8]) -> Option<u64> {
+    std::str::from_utf8(value).ok()?.trim().parse().ok()
+}
+
+fn malformed() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "malformed sparse map")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(SparseVersion::parse("0.0"), Some(SparseVersion::V0_0));
+        assert_eq!(SparseVersion::parse("0.1"), Some(SparseVersion::V0_1));
+        assert_eq!(SparseVersion::parse("1"), Some(SparseVersion::V1_0));
+        assert_eq!(SparseVersion::parse("1.1"), None);
+        assert_eq!(SparseVersion::parse("x"), None);
+    }
+
+    #[test]
+    fn test_align_regions() {
+        assert_eq!(
+            align_regions(&[(100, 10), (600, 10), (4096, 4096)], 100_000),
+            [(0, 1024), (4096, 4096), (100_000, 0)]
+        );
+        // Data reaching the end of the file needs no closing region.
+        assert_eq!(align_regions(&[(4096, 100)], 4196), [(4096, 100)]);
+        assert_eq!(align_regions(&[], 4096), [(4096, 0)]);
+    }
+
+    #[test]
+    fn test_map_block_roundtrip() {
+        // Enough regions to spill the map over several blocks
+        let regions: Vec<_> = (0..100u64).map(|i| (i * 1_000_000_000, 512)).collect();
+        let map = SparseMap {
+            regions: regions.clone(),
+            size: 200_000_000_000,
+        };
+        let mut data = map_block(&map);
+        assert_eq!(data.len() % 512, 0);
+        assert!(data.len() > 512);
+        data.extend_from_slice(b"file data");
+
+        let mut reader = data.as_slice();
+        assert_eq!(read_map_block(&mut reader).unwrap(), regions);
+        assert_eq!(reader, b"file data");
+    }
+
+    #[test]
+    fn test_expand() {
+        let map = SparseMap {
+            regions: vec![(2, 3), (8, 1), (12, 0)],
+            size: 12,
+        };
+        let mut out = Vec::new();
+        expand(&mut &b"abcd"[..], &mut out, &map).unwrap();
+        assert_eq!(out, b"\0\0abc\0\0\0d\0\0\0");
+    }
+
+    #[test]
+    fn test_map_block_rejects_garbage() {
+        let mut data = b"2\n0\nten\n".to_vec();
+        data.resize(512, 0);
+        assert!(read_map_block(&mut data.as_slice()).is_err());
+    }
+}
@@ -3,17 +3,41 @@
 // For the full copyright and license information, please view the LICENSE
 // file that was distributed with this source code.
 
+pub mod acls;
+pub mod command;
+pub mod confirm;
+pub mod date;
 pub mod errors;
+pub mod format;
+pub mod incremental;
+pub mod member;
 pub mod operations;
-
-use clap::{arg, crate_version, ArgAction, Command};
+pub mod pax;
+pub mod safety;
+pub mod selinux;
+pub mod sparse;
+pub mod volume;
+pub mod xattrs;
+
+use clap::{arg, crate_version, ArgAction, ArgGroup, Command};
 use std::path::{Path, PathBuf};
+use uucore::backup_control::{self, BackupMode};
 use uucore::error::UResult;
 use uucore::format_usage;
 
 const ABOUT: &str = "an archiving utility";
 const USAGE: &str = "tar key [FILE...]\n       tar {-c|-t|-x} [-v] -f ARCHIVE [FILE...]";
 
+/// Options choosing what extraction does about existing files, of which
+/// only one may be given
+const OLD_FILES_OPTIONS: [&str; 5] = [
+    "keep-old-files",
+    "keep-newer-files",
+    "skip-old-files",
+    "overwrite",
+    "unlink-first",
+];
+
 /// Determines whether a string looks like a POSIX tar keystring.
 ///
 /// A valid keystring must not start with '-', must contain at least one
@@ -62,9 +86,7 @@ fn expand_posix_keystring(args: Vec<std::ffi::OsString>) -> Vec<std::ffi::OsStri
                 }
             }
             'b' => {
-                // Preserve parity with dash-style parsing by forwarding '-b'
-                // and its operand (when present). Since '-b' is currently
-                // unsupported, clap will report it as an unknown argument.
+                // Next file operand is the blocking factor
                 result.push(std::ffi::OsString::from("-b"));
                 if file_idx < file_operands.len() {
                     result.push(file_operands[file_idx].clone());
@@ -82,6 +104,234 @@ fn expand_posix_keystring(args: Vec<std::ffi::OsString>) -> Vec<std::ffi::OsStri
     result
 }
 
+/// Reads the `SOURCE_DATE_EPOCH` environment variable used by reproducible
+/// builds, returning `None` when it is unset or empty.
+fn source_date_epoch() -> UResult<Option<u64>> {
+    match std::env::var("SOURCE_DATE_EPOCH") {
+        Ok(value) if !value.is_empty() => value.trim().parse().map(Some).map_err(|_| {
+            uucore::error::USimpleError::new(
+                2,
+                format!("Invalid SOURCE_DATE_EPOCH value: '{value}'"),
+            )
+        }),
+        _ => Ok(None),
+    }
+}
+
+/// Builds the extended attribute filter, or `None` when xattrs are not
+/// handled. `--xattrs-include`/`--xattrs-exclude` imply `--xattrs`.
+fn xattr_filter(matches: &clap::ArgMatches) -> UResult<Option<xattrs::XattrFilter>> {
+    let patterns = |id: &str| -> Vec<String> {
+        matches
+            .get_many::<String>(id)
+            .map(|v| v.cloned().collect())
+            .unwrap_or_default()
+    };
+    let include = patterns("xattrs-include");
+    let exclude = patterns("xattrs-exclude");
+
+    let enabled = matches.get_flag("xattrs") || !include.is_empty() || !exclude.is_empty();
+    if !enabled || matches.get_flag("no-xattrs") {
+        return Ok(None);
+    }
+
+    xattrs::XattrFilter::new(&include, &exclude)
+        .map(Some)
+        .map_err(|e| uucore::error::USimpleError::new(2, format!("Invalid xattrs pattern: {e}")))
+}
+
+/// Returns the sparse encoding to write, or `None` without `-S`.
+/// `--sparse-version` implies `--sparse`.
+fn sparse_version(matches: &clap::ArgMatches) -> UResult<Option<sparse::SparseVersion>> {
+    match matches.get_one::<String>("sparse-version") {
+        Some(value) => sparse::SparseVersion::parse(value)
+            .map(Some)
+            .ok_or_else(|| {
+                uucore::error::USimpleError::new(
+                    2,
+                    format!("Invalid sparse version value: '{value}'"),
+                )
+            }),
+        None => Ok(matches
+            .get_flag("sparse")
+            .then(sparse::SparseVersion::default)),
+    }
+}
+
+/// Works out the record size from `-b` or `--record-size`. Each overrides
+/// the other, so at most one is set.
+fn blocking(matches: &clap::ArgMatches) -> UResult<volume::Blocking> {
+    let record_size = if let Some(value) = matches.get_one::<String>("blocking-factor") {
+        value
+            .parse::<u64>()
+            .ok()
+            .filter(|&factor| factor > 0)
+            .and_then(|factor| factor.checked_mul(512))
+            .ok_or_else(|| format!("{value}: Invalid blocking factor"))
+    } else if let Some(value) = matches.get_one::<String>("record-size") {
+        match volume::parse_size(value, 1) {
+            Some(size) if size > 0 && size % 512 == 0 => Ok(size),
+            Some(_) => Err("Record size must be a multiple of 512.".to_string()),
+            None => Err(format!("{value}: Invalid record size")),
+        }
+    } else {
+        Ok(volume::RECORD_SIZE)
+    };
+
+    Ok(volume::Blocking {
+        record_size: record_size.map_err(|e| uucore::error::USimpleError::new(2, e))?,
+        read_full_records: matches.get_flag("read-full-records"),
+    })
+}
+
+/// Works out how to back up files replaced by extraction. `--suffix`
+/// implies `--backup`.
+fn backup_mode(matches: &clap::ArgMatches) -> UResult<BackupMode> {
+    // tar has no `-b` for backups, so uucore is only asked when `--backup`
+    // was given.
+    if matches.contains_id(backup_control::arguments::OPT_BACKUP) {
+        backup_control::determine_backup_mode(matches)
+            .map_err(|e| uucore::error::USimpleError::new(2, e.to_string()))
+    } else if matches.contains_id(backup_control::arguments::OPT_SUFFIX) {
+        Ok(BackupMode::Existing)
+    } else {
+        Ok(BackupMode::None)
+    }
+}
+
+/// Works out the time before which files are left out of a new archive.
+/// A date starting with `/` or `.`, or failing that one naming an existing
+/// file, stands for the file's modification time.
+fn newer_than(
+    matches: &clap::ArgMatches,
+    verbose: bool,
+) -> UResult<Option<operations::create::NewerThan>> {
+    let (option, text, mtime_only) = if let Some(text) = matches.get_one::<String>("newer") {
+        ("--after-date", text, false)
+    } else if let Some(text) = matches.get_one::<String>("newer-mtime") {
+        ("--newer-mtime", text, true)
+    } else {
+        return Ok(None);
+    };
+
+    let is_file = text.starts_with(['/', '.']);
+    let time = match date::parse_date(text).filter(|_| !is_file) {
+        Some(time) => {
+            let treated_as = date::format_time(time, true, matches.get_flag("utc"));
+            if verbose && treated_as != *text {
+                uucore::show_error!("Option {option}: Treating date '{text}' as {treated_as}");
+            }
+            time
+        }
+        None => match std::fs::metadata(text) {
+            Ok(meta) => filetime::FileTime::from_last_modification_time(&meta),
+            Err(e) if is_file => {
+                uucore::show_error!("{text}: Cannot stat: {e}");
+                return Err(uucore::error::USimpleError::new(
+                    2,
+                    "Date sample file not found",
+                ));
+            }
+            // Like GNU tar, archive everything rather than give up.
+            Err(_) => {
+                let oldest = i64::MIN + 1;
+                uucore::show_error!("Substituting {oldest} for unknown date format '{text}'");
+                filetime::FileTime::from_unix_time(oldest, 0)
+            }
+        },
+    };
+    Ok(Some(operations::create::NewerThan { time, mtime_only }))
+}
+
+/// Collects the incremental dump settings, or `None` without `-g` or `-G`.
+fn incremental_options(
+    matches: &clap::ArgMatches,
+) -> UResult<Option<incremental::IncrementalOptions>> {
+    let level = matches
+        .get_one::<String>("level")
+        .map(|level| level.parse::<u32>())
+        .transpose()
+        .map_err(|_| uucore::error::USimpleError::new(2, "Invalid incremental level value"))?;
+    let snapshot = matches.get_one::<PathBuf>("listed-incremental").cloned();
+    if level.is_some() && snapshot.is_none() {
+        uucore::show_error!("--level is meaningless without --listed-incremental");
+    }
+    if snapshot.is_none() && !matches.get_flag("incremental") {
+        return Ok(None);
+    }
+    Ok(Some(incremental::IncrementalOptions {
+        snapshot,
+        level_zero: level == Some(0),
+        check_device: !matches.get_flag("no-check-device"),
+    }))
+}
+
+/// Collects the multi-volume settings, or `None` without `-M`. `-L` and
+/// `-F` imply `-M`.
+fn volume_options(
+    matches: &clap::ArgMatches,
+    blocking: volume::Blocking,
+) -> UResult<Option<volume::VolumeOptions>> {
+    let tape_length = match matches.get_one::<String>("tape-length") {
+        Some(value) => {
+            let length = volume::parse_size(value, 1024).ok_or_else(|| {
+                uucore::error::USimpleError::new(2, format!("Invalid tape length: '{value}'"))
+            })?;
+            if length < blocking.record_size {
+                return Err(uucore::error::USimpleError::new(
+                    2,
+                    "Volume length cannot be less than record size",
+                ));
+            }
+            Some(length)
+        }
+        None => None,
+    };
+    let info_script = matches.get_one::<String>("info-script").cloned();
+
+    if !matches.get_flag("multi-volume") && tape_length.is_none() && info_script.is_none() {
+        return Ok(None);
+    }
+    Ok(Some(volume::VolumeOptions {
+        archives: matches
+            .get_many::<PathBuf>("file")
+            .map(|v| v.cloned().collect())
+            .unwrap_or_default(),
+        tape_length,
+        info_script,
+        volno_file: matches.get_one::<PathBuf>("volno-file").cloned(),
+    }))
+}
+
+/// Picks the format of a new archive. Extended attributes, ACLs and SELinux
+/// contexts are stored in PAX records, so they select `posix` unless another
+/// format was requested explicitly.
+fn archive_format(
+    matches: &clap::ArgMatches,
+    pax_option: Option<&str>,
+) -> UResult<format::ArchiveFormat> {
+    let requested = if matches.get_flag("posix") {
+        Some(format::ArchiveFormat::Posix)
+    } else if matches.get_flag("old-archive") {
+        Some(format::ArchiveFormat::V7)
+    } else {
+        matches
+            .get_one::<String>("format")
+            .and_then(|name| format::ArchiveFormat::from_name(name))
+    };
+
+    match (requested, pax_option) {
+        (None, Some(_)) => Ok(format::ArchiveFormat::Posix),
+        (Some(requested), Some(option)) if requested != format::ArchiveFormat::Posix => {
+            Err(uucore::error::USimpleError::new(
+                2,
+                format!("{option} can be used only on POSIX archives"),
+            ))
+        }
+        (requested, _) => Ok(requested.unwrap_or_default()),
+    }
+}
+
 #[uucore::main]
 pub fn uumain(args: impl uucore::Args) -> UResult<()> {
     // Collect args - the test framework may add util_name as args[1], so skip it if present
@@ -130,8 +380,24 @@ pub fn uumain(args: impl uucore::Args) -> UResult<()> {
         }
     };
 
-    let verbose = matches.get_flag("verbose");
+    let verbose = matches.get_count("verbose");
+    let listing = operations::list::ListingFormat {
+        full_time: matches.get_flag("full-time"),
+        utc: matches.get_flag("utc"),
+        numeric_owner: matches.get_flag("numeric-owner"),
+    };
+    let block_number = matches.get_flag("block-number");
+    let index_file = matches.get_one::<PathBuf>("index-file").cloned();
     let allow_absolute = matches.get_flag("absolute-names");
+    let xattrs = xattr_filter(&matches)?;
+    let acls = matches.get_flag("acls");
+    let selinux = matches.get_flag("selinux");
+    let blocking = blocking(&matches)?;
+    let multi_volume = volume_options(&matches, blocking)?;
+    let label = matches.get_one::<String>("label").cloned();
+    let ignore_zeros = matches.get_flag("ignore-zeros");
+    let interactive = matches.get_flag("interactive");
+    let incremental = incremental_options(&matches)?;
 
     // Handle extract operation
     if matches.get_flag("extract") {
@@ -139,7 +405,47 @@ pub fn uumain(args: impl uucore::Args) -> UResult<()> {
             uucore::error::USimpleError::new(64, "option requires an argument -- 'f'")
         })?;
 
-        return operations::extract::extract_archive(archive_path, verbose);
+        let old_files = if matches.get_flag("keep-old-files") {
+            operations::extract::OldFiles::Keep
+        } else if matches.get_flag("skip-old-files") {
+            operations::extract::OldFiles::Skip
+        } else if matches.get_flag("keep-newer-files") {
+            operations::extract::OldFiles::KeepNewer
+        } else if matches.get_flag("unlink-first") {
+            operations::extract::OldFiles::UnlinkFirst
+        } else {
+            // `--overwrite` asks for what is done anyway.
+            operations::extract::OldFiles::Overwrite
+        };
+
+        let options = operations::extract::ExtractOptions {
+            absolute_names: allow_absolute,
+            old_files,
+            recursive_unlink: matches.get_flag("recursive-unlink"),
+            keep_directory_symlink: matches.get_flag("keep-directory-symlink"),
+            to_stdout: matches.get_flag("to-stdout"),
+            to_command: matches.get_one::<String>("to-command").cloned(),
+            ignore_command_error: matches.get_flag("ignore-command-error"),
+            backup: backup_mode(&matches)?,
+            backup_suffix: backup_control::determine_backup_suffix(&matches),
+            touch: matches.get_flag("touch"),
+            atime_preserve: matches.get_flag("atime-preserve"),
+            xattrs,
+            acls,
+            selinux,
+            blocking,
+            multi_volume,
+            label,
+            ignore_zeros,
+            incremental: incremental.is_some(),
+            interactive,
+            verbose,
+            listing,
+            block_number,
+            index_file,
+        };
+
+        return operations::extract::extract_archive(archive_path, &options);
     }
 
     // Handle create operation
@@ -160,7 +466,78 @@ pub fn uumain(args: impl uucore::Args) -> UResult<()> {
             ));
         }
 
-        return operations::create::create_archive(archive_path, &files, allow_absolute, verbose);
+        let sort = match matches.get_one::<String>("sort").map(String::as_str) {
+            Some("name") => operations::create::SortOrder::Name,
+            Some("inode") => operations::create::SortOrder::Inode,
+            _ => operations::create::SortOrder::None,
+        };
+
+        let reproducible = matches.get_flag("reproducible");
+        let mtime_clamp = if reproducible {
+            source_date_epoch()?
+        } else {
+            None
+        };
+
+        let pax_arguments: Vec<&String> = matches
+            .get_many::<String>("pax-option")
+            .map(|v| v.collect())
+            .unwrap_or_default();
+        let pax = pax::PaxOptions::parse(&pax_arguments)
+            .map_err(|e| uucore::error::USimpleError::new(2, e))?;
+
+        let pax_option = [
+            (!pax_arguments.is_empty(), "--pax-option"),
+            (xattrs.is_some(), "--xattrs"),
+            (acls, "--acls"),
+            (selinux, "--selinux"),
+        ]
+        .into_iter()
+        .find_map(|(enabled, option)| enabled.then_some(option));
+        let format = archive_format(&matches, pax_option)?;
+        let sparse = sparse_version(&matches)?;
+        // POSIX archives record labels in a global header, but only the
+        // GNU formats can be split over volumes.
+        let gnu_features = multi_volume.is_some()
+            || incremental.is_some()
+            || (label.is_some() && format != format::ArchiveFormat::Posix);
+        if gnu_features && !format.is_gnu() {
+            return Err(uucore::error::USimpleError::new(
+                2,
+                "GNU features wanted on incompatible archive format",
+            ));
+        }
+        if let Some(label) = label.as_ref().filter(|label| label.len() > 99) {
+            return Err(uucore::error::USimpleError::new(
+                2,
+                format!("{label}: Volume label is too long (limit is 99 bytes)"),
+            ));
+        }
+
+        let options = operations::create::CreateOptions {
+            allow_absolute,
+            sort,
+            reproducible,
+            mtime_clamp,
+            xattrs,
+            acls,
+            selinux,
+            format,
+            pax,
+            sparse,
+            blocking,
+            multi_volume,
+            label,
+            newer: newer_than(&matches, verbose > 0)?,
+            incremental,
+            interactive,
+            verbose,
+            listing,
+            block_number,
+            index_file,
+        };
+
+        return operations::create::create_archive(archive_path, &files, &options);
     }
 
     // Handle list operation
@@ -169,7 +546,19 @@ pub fn uumain(args: impl uucore::Args) -> UResult<()> {
             uucore::error::USimpleError::new(64, "option requires an argument -- 'f'")
         })?;
 
-        return operations::list::list_archive(archive_path, verbose);
+        let options = operations::list::ListOptions {
+            blocking,
+            multi_volume,
+            label,
+            ignore_zeros,
+            interactive,
+            verbose,
+            listing,
+            block_number,
+            index_file,
+        };
+
+        return operations::list::list_archive(archive_path, &options);
     }
 
     // If no operation specified, show error
@@ -187,6 +576,8 @@ pub fn uu_app() -> Command {
         .override_usage(format_usage(USAGE))
         .infer_long_args(true)
         .disable_help_flag(true)
+        // -V is --label, as in GNU tar.
+        .disable_version_flag(true)
         .args([
             // Main operation modes
             arg!(-c --create "Create a new archive").conflicts_with_all(["extract", "list"]),
@@ -199,26 +590,103 @@ pub fn uu_app() -> Command {
                 .conflicts_with_all(["create", "list"]),
             // Archive file
             arg!(-f --file <ARCHIVE> "Use archive file or device ARCHIVE")
+                .action(ArgAction::Append)
+                .value_parser(clap::value_parser!(PathBuf)),
+            arg!(-b --"blocking-factor" <BLOCKS> "BLOCKS x 512 bytes per record")
+                .overrides_with("record-size"),
+            arg!(--"record-size" <NUMBER> "NUMBER of bytes per record, multiple of 512")
+                .overrides_with("blocking-factor"),
+            arg!(-B --"read-full-records" "Reblock as we read (for 4.2BSD pipes)"),
+            arg!(-i --"ignore-zeros" "Ignore zeroed blocks in archive (means EOF)"),
+            arg!(-M --"multi-volume" "Create, list or extract a multi-volume archive"),
+            arg!(-L --"tape-length" <N> "Change volume after writing N*1024 bytes (implies -M)"),
+            arg!(-F --"info-script" <NAME> "Run script at end of each volume (implies -M)")
+                .alias("new-volume-script"),
+            arg!(--"volno-file" <FILE> "Keep track of the volume number in FILE")
                 .value_parser(clap::value_parser!(PathBuf)),
+            arg!(-V --label <TEXT> "Create archive with volume name TEXT; at list/extract time, use TEXT as a globbing pattern for volume name"),
             arg!(
                 -P --"absolute-names"
                 "Don't strip leading '/'s from file names"
             ),
+            arg!(--sort <ORDER> "Directory sorting order: none, name or inode")
+                .value_parser(["none", "name", "inode"]),
+            arg!(
+                --reproducible
+                "Create a bit-identical archive: sort names, clamp mtimes to SOURCE_DATE_EPOCH, zero owners"
+            ),
+            arg!(-N --newer <DATE> "Only store files newer than DATE, or than the file DATE")
+                .visible_alias("after-date"),
+            arg!(--"newer-mtime" <DATE> "Only store files whose data changed after DATE"),
+            arg!(-g --"listed-incremental" <FILE> "Handle new GNU-format incremental backups")
+                .value_parser(clap::value_parser!(PathBuf)),
+            arg!(-G --incremental "Handle old GNU-format incremental backups"),
+            arg!(--level <NUMBER> "Dump level for created listed-incremental archive"),
+            arg!(--"no-check-device" "Do not check device numbers when creating incremental archives"),
+            arg!(-k --"keep-old-files" "Don't replace existing files when extracting, treat them as errors"),
+            arg!(--"keep-newer-files" "Don't replace existing files that are newer than their archive copies"),
+            arg!(--"skip-old-files" "Don't replace existing files when extracting, silently skip over them"),
+            arg!(--overwrite "Overwrite existing files when extracting (the default; accepted for compatibility)"),
+            arg!(-U --"unlink-first" "Remove each file prior to extracting over it"),
+            arg!(--"recursive-unlink" "Empty hierarchies prior to extracting directory"),
+            arg!(--"keep-directory-symlink" "Preserve existing symlinks to directories when extracting"),
+            arg!(-O --"to-stdout" "Extract files to standard output"),
+            arg!(--"to-command" <COMMAND> "Pipe extracted files to another program"),
+            arg!(--"ignore-command-error" "Ignore exit codes of children"),
+            backup_control::arguments::backup()
+                .help("Back up files before removal, choosing the version CONTROL"),
+            arg!(--suffix <SUFFIX> "Back up files before removal, overriding the usual suffix ('~')")
+                .id(backup_control::arguments::OPT_SUFFIX)
+                .allow_hyphen_values(true),
+            arg!(-m --touch "Don't extract file modified time"),
+            arg!(--"atime-preserve" "Restore archived access times when extracting"),
+            arg!(--xattrs "Enable extended attributes support; without --xattrs-include only user.* and security.capability are extracted").overrides_with("no-xattrs"),
+            arg!(--"no-xattrs" "Disable extended attributes support").overrides_with("xattrs"),
+            arg!(--"xattrs-include" <MASK> "Specify the include pattern for xattr keys")
+                .action(ArgAction::Append),
+            arg!(--"xattrs-exclude" <MASK> "Specify the exclude pattern for xattr keys")
+                .action(ArgAction::Append),
+            arg!(--acls "Enable POSIX ACLs support").overrides_with("no-acls"),
+            arg!(--"no-acls" "Disable POSIX ACLs support").overrides_with("acls"),
+            arg!(--selinux "Enable SELinux context support").overrides_with("no-selinux"),
+            arg!(--"no-selinux" "Disable SELinux context support").overrides_with("selinux"),
+            arg!(-H --format <FORMAT> "Create archive of the given format: v7, oldgnu, gnu, ustar or posix")
+                .value_parser(["v7", "oldgnu", "gnu", "ustar", "posix", "pax"]),
+            arg!(--"old-archive" "Same as --format=v7")
+                .visible_alias("portability")
+                .conflicts_with_all(["format", "posix"]),
+            arg!(--posix "Same as --format=posix").conflicts_with("format"),
+            arg!(--"pax-option" <LIST> "Control pax extended headers: keyword[[:]=value][,...]")
+                .action(ArgAction::Append),
+            arg!(-S --sparse "Handle sparse files efficiently"),
+            arg!(--"sparse-version" <VERSION> "Set version of the sparse format to use (implies --sparse)"),
             // Compression options
             // arg!(-z --gzip "Filter through gzip"),
             // arg!(-j --bzip2 "Filter through bzip2"),
             // arg!(-J --xz "Filter through xz"),
             // Common options
-            arg!(-v --verbose "Verbosely list files processed"),
+            arg!(-v --verbose "Verbosely list files processed; twice for a full listing")
+                .action(ArgAction::Count),
+            arg!(--"full-time" "Print file time to its full resolution"),
+            arg!(--utc "Print file modification times in UTC"),
+            arg!(--"numeric-owner" "Always use numbers for user/group names"),
+            arg!(-R --"block-number" "Show block number within archive with each message"),
+            arg!(--"index-file" <FILE> "Send verbose output to FILE")
+                .value_parser(clap::value_parser!(PathBuf)),
+            arg!(-w --interactive "Ask for confirmation for every action")
+                .visible_alias("confirmation"),
             // arg!(-h --dereference "Follow symlinks"),
             // arg!(-p --"preserve-permissions" "Extract information about file permissions"),
             // Help
             arg!(--help "Print help information").action(ArgAction::Help),
+            arg!(--version "Print version information").action(ArgAction::Version),
             // Files to process
             arg!([files]... "Files to archive or extract")
                 .action(ArgAction::Append)
                 .value_parser(clap::value_parser!(PathBuf)),
         ])
+        .group(ArgGroup::new("old-files").args(OLD_FILES_OPTIONS))
+        .group(ArgGroup::new("threshold").args(["newer", "newer-mtime"]))
 }
 
 #[cfg(test)]
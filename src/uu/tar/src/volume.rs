@@ -0,0 +1,1037 @@
+// This file is part of the uutils tar package.
+//
+// For the full copyright and license information, please view the LICENSE
+// file that was distributed with this source code.
+
+//! Archive device I/O: records (`-b`, `--record-size`, `-B`) and
+//! multi-volume archives (`-M`, `-L`, `-F`, `--volno-file`).
+//!
+//! Archives are read and written a record at a time, and the last record
+//! is padded with zeros, as tape drives expect. When a volume is full the
+//! archive carries on in the next one. A member cut off at the break
+//! continues after a GNU `M` header giving its name, the bytes still to
+//! come and the offset they start at. Volumes are named by the `-f`
+//! options in turn; once those run out, the info script or the user
+//! supplies the next name.
+//!
+//! An archive ends with two zero blocks. Reading stops at the first one
+//! unless `-i` asks to skip them, and [`VolumeReader::check_end`] warns
+//! like GNU tar when the second one is missing.
+//!
+//! A volume label (`-V`) is a GNU `V` header at the start of the archive,
+//! repeated with the volume number at the start of every volume of a
+//! multi-volume archive. POSIX archives record it in a global header.
+
+use crate::errors::TarError;
+use crate::format::{
+    parse_numeric, ArchiveFormat, GNU_LONG_NAME, GNU_MULTIVOLUME, GNU_VOLUME_LABEL, NAME_FIELD_SIZE,
+};
+use crate::pax;
+use std::cell::RefCell;
+use std::fs::{self, File};
+use std::io::{self, BufRead, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tar::{Entry, EntryType, Header};
+
+/// Size of the blocks archives are made up of
+pub const BLOCK_SIZE: u64 = 512;
+
+/// PAX global record holding the volume label of a POSIX archive
+pub const LABEL_KEYWORD: &str = "GNU.volume.label";
+
+/// Default size of the records an archive is written in, GNU tar's
+/// blocking factor of 20
+pub const RECORD_SIZE: u64 = 20 * BLOCK_SIZE;
+
+/// How archive data is grouped for reading and writing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Blocking {
+    /// Bytes per record, a multiple of 512 (`-b`, `--record-size`)
+    pub record_size: u64,
+    /// Keep reading until each record is complete, for pipes that deliver
+    /// short reads (`-B`)
+    pub read_full_records: bool,
+}
+
+impl Default for Blocking {
+    fn default() -> Self {
+        Self {
+            record_size: RECORD_SIZE,
+            read_full_records: false,
+        }
+    }
+}
+
+impl Blocking {
+    /// Number of 512-byte blocks per record.
+    pub fn blocking_factor(&self) -> u64 {
+        self.record_size / BLOCK_SIZE
+    }
+}
+
+/// Settings for reading or writing an archive split over several volumes
+#[derive(Debug, Clone, Default)]
+pub struct VolumeOptions {
+    /// Names given with `-f`, used in turn for successive volumes
+    pub archives: Vec<PathBuf>,
+    /// Bytes written to a volume before moving on to the next (`-L`)
+    pub tape_length: Option<u64>,
+    /// Shell command run to prepare each new volume (`-F`)
+    pub info_script: Option<String>,
+    /// File holding the number of the first volume, updated with the
+    /// number of the last one (`--volno-file`)
+    pub volno_file: Option<PathBuf>,
+}
+
+/// Parses a size such as `--tape-length` or `--record-size`: a number of
+/// `unit`-byte units, or a number with a suffix such as `10M`.
+pub fn parse_size(value: &str, unit: u64) -> Option<u64> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (digits, suffix) = value.split_at(split);
+    let unit: u64 = match suffix {
+        "" => unit,
+        "B" | "k" | "K" => 1 << 10,
+        "c" => 1,
+        "w" => 2,
+        "b" => 512,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        "T" => 1 << 40,
+        "P" => 1 << 50,
+        _ => return None,
+    };
+    digits.parse::<u64>().ok()?.checked_mul(unit)
+}
+
+/// Returns the volume label recorded by `entry`, reading the data of PAX
+/// global headers to find it. Other entries have no label.
+pub fn volume_label<R: Read>(entry: &mut Entry<R>) -> io::Result<Option<String>> {
+    let entry_type = entry.header().entry_type();
+    if entry_type.as_byte() == GNU_VOLUME_LABEL {
+        return Ok(Some(
+            String::from_utf8_lossy(&entry.path_bytes()).into_owned(),
+        ));
+    }
+    if !entry_type.is_pax_global_extensions() {
+        return Ok(None);
+    }
+
+    let mut data = Vec::new();
+    entry.read_to_end(&mut data)?;
+    Ok(pax::parse_records(&data)
+        .into_iter()
+        .rev()
+        .find(|(keyword, _)| keyword == LABEL_KEYWORD)
+        .map(|(_, value)| String::from_utf8_lossy(&value).into_owned()))
+}
+
+/// Checks the label of the archive being read against the `-V` pattern.
+/// Volumes of a multi-volume archive are labelled `LABEL Volume N`, which
+/// also matches.
+pub fn check_label(label: Option<&str>, pattern: &str, multi_volume: bool) -> Result<(), TarError> {
+    let Some(label) = label else {
+        return Err(TarError::NotLabeled {
+            pattern: pattern.to_string(),
+        });
+    };
+    let matches = |pattern: &str| {
+        glob::Pattern::new(pattern)
+            .map(|p| p.matches(label))
+            .unwrap_or(pattern == label)
+    };
+    if matches(pattern) || (multi_volume && matches(&format!("{pattern} Volume [1-9]*"))) {
+        Ok(())
+    } else {
+        Err(TarError::LabelMismatch {
+            label: label.to_string(),
+            pattern: pattern.to_string(),
+        })
+    }
+}
+
+/// Builds the `V` header labelling a volume, dated `mtime` or else now.
+fn label_header(label: &str, mtime: Option<u64>) -> Header {
+    let name = &label.as_bytes()[..label.len().min(NAME_FIELD_SIZE)];
+    let mtime = mtime.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs())
+    });
+
+    let mut header = Header::new_old();
+    header.as_old_mut().name[..name.len()].copy_from_slice(name);
+    header.set_entry_type(EntryType::new(GNU_VOLUME_LABEL));
+    header.set_size(0);
+    header.set_mtime(mtime);
+    header.set_cksum();
+    header
+}
+
+/// GNU tar leaves the size of a volume label empty, which the tar crate
+/// rejects, so fills it in.
+fn fill_label_size(block: &mut [u8]) {
+    if block[156] != GNU_VOLUME_LABEL || block[124..136].iter().any(|&b| b != 0) {
+        return;
+    }
+    let mut header = Header::new_old();
+    header.as_mut_bytes().copy_from_slice(block);
+    header.set_size(0);
+    header.set_cksum();
+    block.copy_from_slice(header.as_bytes());
+}
+
+/// Which volume is in use and how to get the next one
+#[derive(Debug)]
+struct VolumeState {
+    /// `None` when the archive is a single volume
+    options: Option<VolumeOptions>,
+    /// Names of the volumes, starting with the `-f` names
+    archives: Vec<PathBuf>,
+    /// Index in `archives` of the current volume's name
+    cursor: usize,
+    /// Whether every name has been used, so that the user or the info
+    /// script must provide further volumes
+    looped: bool,
+    /// Number of the current volume
+    volno: u64,
+    /// Option letter of the operation, passed to the info script
+    subcommand: &'static str,
+    format: ArchiveFormat,
+    blocking: Blocking,
+    /// Volume label to write at the start of each volume
+    label: Option<String>,
+    /// mtime of the label headers, instead of the time each is written
+    label_mtime: Option<u64>,
+    /// Why moving on to the next volume failed
+    failure: Option<String>,
+}
+
+impl VolumeState {
+    fn capacity(&self) -> Option<u64> {
+        let length = self.options.as_ref()?.tape_length?;
+        let record_size = self.blocking.record_size;
+        Some(length.div_ceil(record_size).max(1) * record_size)
+    }
+
+    /// Works out the name of the next volume, or of the same one again
+    /// after it turned out to be unusable.
+    fn next_volume(&mut self, retry: bool) -> Result<PathBuf, String> {
+        if !retry {
+            self.volno += 1;
+            self.cursor += 1;
+            if self.cursor == self.archives.len() {
+                self.cursor = 0;
+                self.looped = true;
+            }
+        }
+
+        if self.looped || retry {
+            let script = self.options.as_ref().and_then(|o| o.info_script.clone());
+            match script {
+                Some(script) => {
+                    self.write_volno_file()?;
+                    if let Some(name) = self.run_info_script(&script)? {
+                        self.archives[self.cursor] = name;
+                    }
+                }
+                None => self.prompt()?,
+            }
+        }
+        Ok(self.archives[self.cursor].clone())
+    }
+
+    /// Asks the user to get the next volume ready, reading the reply from
+    /// standard input.
+    fn prompt(&mut self) -> Result<(), String> {
+        let mut input = io::stdin().lock();
+        loop {
+            eprint!(
+                "Prepare volume #{} for '{}' and hit return: ",
+                self.volno,
+                self.archives[self.cursor].display()
+            );
+            let mut reply = String::new();
+            if input.read_line(&mut reply).map_err(|e| e.to_string())? == 0 {
+                return Err("EOF where user reply was expected".to_string());
+            }
+
+            let reply = reply.trim_start();
+            match reply.chars().next() {
+                None | Some('\n' | 'y' | 'Y') => return Ok(()),
+                Some('q' | 'Q') => return Err("No new volume; exiting.".to_string()),
+                Some('n' | 'N') if !reply[1..].trim().is_empty() => {
+                    self.archives[self.cursor] = PathBuf::from(reply[1..].trim());
+                    return Ok(());
+                }
+                Some('!') => {
+                    let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string());
+                    if let Err(e) = Command::new(&shell).status() {
+                        uucore::show_error!("{shell}: Cannot execute: {e}");
+                    }
+                }
+                Some('?') => eprint!(
+                    " n name        Give a new file name for the next (and subsequent) volume(s)\n \
+                     q             Abort tar\n \
+                     y or newline  Continue operation\n \
+                     !             Spawn a subshell\n \
+                     ?             Print this list\n"
+                ),
+                Some(_) => eprintln!("Invalid input. Type ? for help."),
+            }
+        }
+    }
+
+    /// Runs the `-F` script, which may print the next volume's name on the
+    /// descriptor given in `TAR_FD`.
+    fn run_info_script(&self, script: &str) -> Result<Option<PathBuf>, String> {
+        let failed = || format!("'{script}' command failed");
+        let mut command = shell_command(script);
+        command
+            .env("TAR_VERSION", env!("CARGO_PKG_VERSION"))
+            .env("TAR_ARCHIVE", &self.archives[self.cursor])
+            .env("TAR_VOLUME", self.volno.to_string())
+            .env(
+                "TAR_BLOCKING_FACTOR",
+                self.blocking.blocking_factor().to_string(),
+            )
+            .env("TAR_SUBCOMMAND", self.subcommand)
+            .env("TAR_FORMAT", self.format.to_string());
+
+        let (mut reader, writer) = io::pipe().map_err(|_| failed())?;
+        pass_descriptor(&mut command, &writer).map_err(|_| failed())?;
+        let mut child = command.spawn().map_err(|_| failed())?;
+        drop(writer);
+
+        let mut output = String::new();
+        let read = reader.read_to_string(&mut output);
+        let status = child.wait().map_err(|_| failed())?;
+        if read.is_err() || !status.success() {
+            return Err(failed());
+        }
+        let name = output.lines().next().unwrap_or_default().trim();
+        Ok((!name.is_empty()).then(|| PathBuf::from(name)))
+    }
+
+    fn write_volno_file(&self) -> Result<(), String> {
+        let Some(path) = self.options.as_ref().and_then(|o| o.volno_file.as_ref()) else {
+            return Ok(());
+        };
+        fs::write(path, format!("{}\n", self.volno))
+            .map_err(|e| format!("{}: Cannot write: {e}", path.display()))
+    }
+
+    /// The `V` header for the current volume, if the archive is labelled.
+    fn label_header(&self) -> Option<Header> {
+        let label = self.label.as_ref()?;
+        Some(match self.options {
+            Some(_) => label_header(&format!("{label} Volume {}", self.volno), self.label_mtime),
+            None => label_header(label, self.label_mtime),
+        })
+    }
+
+    fn fail(&mut self, message: String) -> io::Error {
+        self.failure = Some(message.clone());
+        io::Error::other(message)
+    }
+}
+
+/// Builds a command running `script` with the shell, as for `-F` and
+/// `--to-command`.
+#[cfg(unix)]
+pub fn shell_command(script: &str) -> Command {
+    let mut command = Command::new("/bin/sh");
+    command.arg("-c").arg(script);
+    command
+}
+
+#[cfg(not(unix))]
+pub fn shell_command(script: &str) -> Command {
+    let mut command = Command::new("cmd");
+    command.arg("/C").arg(script);
+    command
+}
+
+/// Lets the script inherit `writer` and tells it the descriptor number.
+#[cfg(unix)]
+fn pass_descriptor(command: &mut Command, writer: &io::PipeWriter) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let fd = writer.as_raw_fd();
+    // SAFETY: clearing FD_CLOEXEC on a descriptor we own has no other effect.
+    if unsafe { libc::fcntl(fd, libc::F_SETFD, 0) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    command.env("TAR_FD", fd.to_string());
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn pass_descriptor(_command: &mut Command, _writer: &io::PipeWriter) -> io::Result<()> {
+    Ok(())
+}
+
+/// Handle on the volumes of an archive, shared by the reader or writer and
+/// the operation using it
+#[derive(Debug, Clone)]
+pub struct Volumes(Rc<RefCell<VolumeState>>);
+
+impl Volumes {
+    /// Prepares to work through the volumes of the archive whose first
+    /// volume is `archive`. `options` is `None` unless `-M` was given.
+    /// `subcommand` and `format` are passed on to the info script.
+    pub fn new(
+        archive: &Path,
+        blocking: Blocking,
+        options: Option<&VolumeOptions>,
+        subcommand: &'static str,
+        format: ArchiveFormat,
+    ) -> Result<Self, TarError> {
+        let mut archives = options.map(|o| o.archives.clone()).unwrap_or_default();
+        if archives.is_empty() {
+            archives.push(archive.to_path_buf());
+        }
+
+        let volno = match options.and_then(|o| o.volno_file.as_ref()) {
+            Some(path) => match fs::read_to_string(path) {
+                Ok(text) => text.trim().parse().map_err(|_| {
+                    TarError::VolumeChange(format!("{}: Invalid volume number", path.display()))
+                })?,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => 1,
+                Err(e) => return Err(TarError::from_io_error(e, path)),
+            },
+            None => 1,
+        };
+
+        Ok(Self(Rc::new(RefCell::new(VolumeState {
+            options: options.cloned(),
+            archives,
+            cursor: 0,
+            looped: false,
+            volno,
+            subcommand,
+            format,
+            blocking,
+            label: None,
+            label_mtime: None,
+            failure: None,
+        }))))
+    }
+
+    /// Wraps the first volume, already created, for writing. Each volume
+    /// starts with a `V` header when `label` is given, dated `label_mtime`
+    /// or else the time it is written.
+    pub fn writer(
+        &self,
+        file: File,
+        label: Option<&str>,
+        label_mtime: Option<u64>,
+    ) -> VolumeWriter {
+        let record_size = usize::try_from(self.blocking().record_size).unwrap_or_default();
+        let mut state = self.0.borrow_mut();
+        state.label = label.map(str::to_string);
+        state.label_mtime = label_mtime;
+        let mut record = Vec::with_capacity(record_size);
+        if let Some(header) = state.label_header() {
+            record.extend_from_slice(header.as_bytes());
+        }
+        VolumeWriter {
+            file,
+            volumes: self.clone(),
+            offset: record.len() as u64,
+            record,
+            record_size,
+            written: 0,
+            stream: StreamPosition::default(),
+        }
+    }
+
+    /// Wraps the first volume, already opened, for reading.
+    pub fn reader(&self, file: File) -> VolumeReader {
+        VolumeReader {
+            file,
+            volumes: self.clone(),
+            record: Vec::new(),
+            position: 0,
+            offset: 0,
+            stream: StreamPosition::default(),
+        }
+    }
+
+    /// Replaces `error` with the failure to change volumes that caused it,
+    /// if there was one.
+    pub fn explain(&self, error: TarError) -> TarError {
+        match self.0.borrow_mut().failure.take() {
+            Some(message) => TarError::VolumeChange(message),
+            None => error,
+        }
+    }
+
+    /// Records the number of the last volume used in the `--volno-file`.
+    pub fn finish(&self) -> Result<(), TarError> {
+        self.0
+            .borrow()
+            .write_volno_file()
+            .map_err(TarError::VolumeChange)
+    }
+
+    /// Name and number of the volume in use.
+    pub fn current(&self) -> (PathBuf, u64) {
+        let state = self.0.borrow();
+        (state.archives[state.cursor].clone(), state.volno)
+    }
+
+    /// Record size the archive is read or written in.
+    pub fn blocking(&self) -> Blocking {
+        self.0.borrow().blocking
+    }
+
+    fn is_multi_volume(&self) -> bool {
+        self.0.borrow().options.is_some()
+    }
+}
+
+/// Writer that sends whole records to the archive and starts a new volume
+/// whenever the current one is full
+#[derive(Debug)]
+pub struct VolumeWriter {
+    file: File,
+    volumes: Volumes,
+    /// Record being filled
+    record: Vec<u8>,
+    record_size: usize,
+    /// Bytes written to the current volume
+    written: u64,
+    /// Bytes of the archive written so far, leaving out the headers that
+    /// continue it on later volumes
+    offset: u64,
+    stream: StreamPosition,
+}
+
+impl VolumeWriter {
+    /// Number of the block within the archive the next header will take
+    /// up (`-R`).
+    pub fn block(&self) -> u64 {
+        self.offset / BLOCK_SIZE
+    }
+
+    /// Pads the last record with zeros and writes it out. Must be called
+    /// once the archive is complete.
+    pub fn finish(mut self) -> io::Result<()> {
+        if !self.record.is_empty() {
+            self.record.resize(self.record_size, 0);
+            self.write_record()?;
+        }
+        self.file.flush()
+    }
+
+    fn write_record(&mut self) -> io::Result<()> {
+        self.file.write_all(&self.record)?;
+        self.written += self.record.len() as u64;
+        self.record.clear();
+        Ok(())
+    }
+
+    fn next_volume(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        let mut state = self.volumes.0.borrow_mut();
+        let mut retry = false;
+        loop {
+            let path = match state.next_volume(retry) {
+                Ok(path) => path,
+                Err(message) => return Err(state.fail(message)),
+            };
+            match File::create(&path) {
+                Ok(file) => {
+                    self.file = file;
+                    break;
+                }
+                Err(e) => {
+                    uucore::show_error!("{}: Cannot open: {e}", path.display());
+                    retry = true;
+                }
+            }
+        }
+        drop(state);
+
+        self.written = 0;
+        if let Some(header) = self.volumes.0.borrow().label_header() {
+            self.record.extend_from_slice(header.as_bytes());
+        }
+        if let Some(blocks) = self.stream.continuation_headers() {
+            self.record.extend_from_slice(&blocks);
+        }
+        // A long name can take the headers past a small record.
+        while self.record.len() >= self.record_size {
+            let rest = self.record.split_off(self.record_size);
+            self.write_record()?;
+            self.record = rest;
+        }
+        Ok(())
+    }
+}
+
+impl Write for VolumeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.record.is_empty() {
+            let capacity = self.volumes.0.borrow().capacity();
+            if capacity.is_some_and(|capacity| self.written >= capacity) {
+                self.next_volume()?;
+            }
+        }
+
+        let n = buf.len().min(self.record_size - self.record.len());
+        self.record.extend_from_slice(&buf[..n]);
+        self.stream.feed(&buf[..n]);
+        self.offset += n as u64;
+        if self.record.len() == self.record_size {
+            self.write_record()?;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Reader that fetches the archive a record at a time and carries on with
+/// the next volume at the end of each one
+#[derive(Debug)]
+pub struct VolumeReader {
+    file: File,
+    volumes: Volumes,
+    /// Last record read
+    record: Vec<u8>,
+    /// Bytes of `record` already handed out
+    position: usize,
+    /// Bytes handed out since the start of the archive
+    offset: u64,
+    stream: StreamPosition,
+}
+
+impl VolumeReader {
+    /// Reads the next record of the current volume, returning `false` at
+    /// the end of the volume.
+    fn read_record(&mut self) -> io::Result<bool> {
+        let blocking = self.volumes.blocking();
+        self.record
+            .resize(usize::try_from(blocking.record_size).unwrap_or_default(), 0);
+        self.position = 0;
+
+        let mut filled = 0;
+        while filled < self.record.len() {
+            match self.file.read(&mut self.record[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+            // Like GNU tar, carry on until the record holds whole blocks.
+            if !blocking.read_full_records && filled % BLOCK_SIZE as usize == 0 {
+                break;
+            }
+        }
+        // A partial block at the end of the archive is dropped, unless it is
+        // all there is.
+        if self.offset > 0 || filled >= BLOCK_SIZE as usize {
+            filled -= filled % BLOCK_SIZE as usize;
+        }
+        self.record.truncate(filled);
+        Ok(filled > 0)
+    }
+
+    fn next_volume(&mut self) -> io::Result<()> {
+        let mut retry = false;
+        loop {
+            let path = {
+                let mut state = self.volumes.0.borrow_mut();
+                match state.next_volume(retry) {
+                    Ok(path) => path,
+                    Err(message) => return Err(state.fail(message)),
+                }
+            };
+            retry = true;
+
+            match File::open(&path) {
+                Ok(file) => self.file = file,
+                Err(e) => {
+                    uucore::show_error!("{}: Cannot open: {e}", path.display());
+                    continue;
+                }
+            }
+            match self.start_volume() {
+                Ok(()) => return Ok(()),
+                Err(message) => uucore::show_warning!("{message}"),
+            }
+        }
+    }
+
+    /// Reads the first record of a new volume, skipping its label and
+    /// checking that it continues the member the previous volume cut off.
+    fn start_volume(&mut self) -> Result<(), String> {
+        self.record.clear();
+        self.position = 0;
+        let mut block = self.next_block()?;
+        if block[156] == GNU_VOLUME_LABEL {
+            self.position += BLOCK_SIZE as usize;
+            block = self.next_block()?;
+        }
+        if self.stream.remaining == 0 {
+            return Ok(());
+        }
+
+        let expected = String::from_utf8_lossy(&self.stream.name).into_owned();
+        let mut name = None;
+        if block[156] == GNU_LONG_NAME {
+            let size = parse_numeric(&block[124..136]);
+            let mut data = Vec::new();
+            while (data.len() as u64) < size {
+                self.position += BLOCK_SIZE as usize;
+                data.extend_from_slice(&self.next_block()?);
+            }
+            self.position += BLOCK_SIZE as usize;
+            name = Some(trim_name(
+                &data[..usize::try_from(size).unwrap_or_default()],
+            ));
+            block = self.next_block()?;
+        }
+        let name = name.unwrap_or_else(|| trim_name(&block[..NAME_FIELD_SIZE]));
+        // GNU tar cuts long names short in the `M` header.
+        let truncated = name.len() == NAME_FIELD_SIZE && self.stream.name.starts_with(&name);
+        if block[156] != GNU_MULTIVOLUME || (name != self.stream.name && !truncated) {
+            return Err(format!("'{expected}' is not continued on this volume"));
+        }
+        let size = parse_numeric(&block[124..136]);
+        let offset = parse_numeric(&block[369..381]);
+        let done = self.stream.size - self.stream.remaining;
+        if size != self.stream.remaining || offset != done {
+            return Err(format!(
+                "This volume is out of sequence ({} - {} != {offset})",
+                self.stream.size, self.stream.remaining
+            ));
+        }
+        if name != self.stream.name {
+            uucore::show_error!(
+                "'{expected}' is possibly continued on this volume: header contains truncated name"
+            );
+        }
+        self.position += BLOCK_SIZE as usize;
+        Ok(())
+    }
+
+    /// Checks the end of an archive read without `-i`. If reading stopped
+    /// at a zero block that is not followed by another, GNU tar warns with
+    /// the number of the block after it.
+    pub fn check_end(&mut self) -> io::Result<()> {
+        if !self.stream.at_header() || !self.stream.zero_block {
+            return Ok(());
+        }
+        let mut block = Vec::with_capacity(BLOCK_SIZE as usize);
+        while block.len() < BLOCK_SIZE as usize {
+            if self.position == self.record.len() && !self.read_record()? {
+                break;
+            }
+            let n = (BLOCK_SIZE as usize - block.len()).min(self.record.len() - self.position);
+            block.extend_from_slice(&self.record[self.position..self.position + n]);
+            self.position += n;
+        }
+        if block.len() < BLOCK_SIZE as usize || block.iter().any(|&b| b != 0) {
+            uucore::show_error!("A lone zero block at {}", self.offset / BLOCK_SIZE);
+        }
+        Ok(())
+    }
+
+    /// Number of the zero block reading stopped at, if it stopped at one.
+    pub fn zero_block(&self) -> Option<u64> {
+        (self.stream.at_header() && self.stream.zero_block).then(|| self.offset / BLOCK_SIZE - 1)
+    }
+
+    /// Number of whole blocks read so far.
+    pub fn blocks_read(&self) -> u64 {
+        self.offset / BLOCK_SIZE
+    }
+
+    /// Returns a copy of the block at the current position of a volume
+    /// being started, reading another record if needed.
+    fn next_block(&mut self) -> Result<Vec<u8>, String> {
+        if self.position == self.record.len() {
+            self.read_record()
+                .map_err(|e| format!("Cannot read volume: {e}"))?;
+        }
+        self.record
+            .get(self.position..self.position + BLOCK_SIZE as usize)
+            .map(<[u8]>::to_vec)
+            .ok_or_else(|| "Cannot read volume: unexpected end of file".to_string())
+    }
+}
+
+impl Read for VolumeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        while self.position == self.record.len() {
+            if !self.read_record()? {
+                if !self.volumes.is_multi_volume() {
+                    return Ok(0);
+                }
+                self.next_volume()?;
+            }
+        }
+
+        if self.stream.at_header() {
+            if let Some(block) = self
+                .record
+                .get_mut(self.position..self.position + BLOCK_SIZE as usize)
+            {
+                fill_label_size(block);
+            }
+        }
+
+        let n = buf.len().min(self.record.len() - self.position);
+        buf[..n].copy_from_slice(&self.record[self.position..self.position + n]);
+        self.position += n;
+        self.offset += n as u64;
+        self.stream.feed(&buf[..n]);
+        Ok(n)
+    }
+}
+
+/// Follows the headers of an archive stream to know which member's data
+/// a volume break falls in.
+#[derive(Debug, Default)]
+struct StreamPosition {
+    /// Partial header block
+    block: Vec<u8>,
+    /// Name of the last member, taken from a GNU `L` header or a PAX
+    /// `path` record before it if there was one
+    name: Vec<u8>,
+    /// Long name for the next member
+    long_name: Option<Vec<u8>>,
+    /// Type and data so far of the `L` or `x` header being read
+    capture: Option<(u8, Vec<u8>)>,
+    /// Data size of the last header's member
+    size: u64,
+    /// Data bytes of that member still to come
+    remaining: u64,
+    /// Padding after the data
+    padding: u64,
+    /// Whether GNU sparse extension blocks come before the data
+    extended: bool,
+    /// Whether the last header block was all zeros
+    zero_block: bool,
+}
+
+impl StreamPosition {
+    fn feed(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            if self.remaining > 0 || self.padding > 0 {
+                // Only the data of an `L` or `x` header is captured, and the
+                // capture ends with it.
+                let counter = if self.remaining > 0 {
+                    &mut self.remaining
+                } else {
+                    &mut self.padding
+                };
+                let n = data
+                    .len()
+                    .min(usize::try_from(*counter).unwrap_or(usize::MAX));
+                *counter -= n as u64;
+                if let Some((_, buffer)) = &mut self.capture {
+                    buffer.extend_from_slice(&data[..n]);
+                }
+                data = &data[n..];
+                if self.remaining == 0 {
+                    self.finish_capture();
+                }
+                continue;
+            }
+
+            let n = data.len().min(BLOCK_SIZE as usize - self.block.len());
+            self.block.extend_from_slice(&data[..n]);
+            data = &data[n..];
+            if self.block.len() == BLOCK_SIZE as usize {
+                let block = std::mem::take(&mut self.block);
+                self.process_block(&block);
+            }
+        }
+    }
+
+    fn process_block(&mut self, block: &[u8]) {
+        if self.extended {
+            // Sparse extension blocks chain through their last byte.
+            self.extended = block[504] != 0;
+            if !self.extended {
+                self.start_data();
+            }
+            return;
+        }
+        self.zero_block = block.iter().all(|&b| b == 0);
+        if self.zero_block {
+            return;
+        }
+
+        let name = trim_name(&block[..NAME_FIELD_SIZE]);
+        self.size = parse_numeric(&block[124..136]);
+        match block[156] {
+            kind @ (GNU_LONG_NAME | b'x') => {
+                self.name = name;
+                self.capture = Some((kind, Vec::new()));
+            }
+            _ => self.name = self.long_name.take().unwrap_or(name),
+        }
+        if block[156] == b'S' && block[482] != 0 {
+            self.extended = true;
+        } else {
+            self.start_data();
+            if self.remaining == 0 {
+                self.finish_capture();
+            }
+        }
+    }
+
+    /// Takes the long name for the next member from the `L` or `x` header
+    /// just read.
+    fn finish_capture(&mut self) {
+        let Some((kind, data)) = self.capture.take() else {
+            return;
+        };
+        if kind == GNU_LONG_NAME {
+            self.long_name = Some(trim_name(&data));
+        } else if let Some((_, path)) = pax::parse_records(&data)
+            .into_iter()
+            .rev()
+            .find(|(key, _)| key == "path")
+        {
+            self.long_name = Some(path);
+        }
+    }
+
+    /// Whether the next byte starts a header block.
+    fn at_header(&self) -> bool {
+        self.remaining == 0 && self.padding == 0 && self.block.is_empty() && !self.extended
+    }
+
+    fn start_data(&mut self) {
+        self.remaining = self.size;
+        self.padding = self.size.div_ceil(BLOCK_SIZE) * BLOCK_SIZE - self.size;
+    }
+
+    /// The headers that start a volume in the middle of a member: the `M`
+    /// header, after an `L` header when the name does not fit in it.
+    fn continuation_headers(&self) -> Option<Vec<u8>> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let mut blocks = Vec::new();
+        if self.name.len() >= NAME_FIELD_SIZE {
+            let size = self.name.len() as u64 + 1;
+            let mut header = Header::new_gnu();
+            header.as_old_mut().name[..13].copy_from_slice(b"././@LongLink");
+            header.set_mode(0o644);
+            header.set_uid(0);
+            header.set_gid(0);
+            header.set_mtime(0);
+            header.set_entry_type(EntryType::GNULongName);
+            header.set_size(size);
+            header.set_cksum();
+            blocks.extend_from_slice(header.as_bytes());
+            blocks.extend_from_slice(&self.name);
+            blocks.resize(
+                blocks.len().div_ceil(BLOCK_SIZE as usize) * BLOCK_SIZE as usize,
+                0,
+            );
+        }
+
+        // Like GNU tar, fill in only the name, type, size and offset.
+        let name = &self.name[..self.name.len().min(NAME_FIELD_SIZE)];
+        let mut header = Header::new_old();
+        header.as_old_mut().mtime = [0; 12];
+        header.as_old_mut().name[..name.len()].copy_from_slice(name);
+        header.set_entry_type(EntryType::new(GNU_MULTIVOLUME));
+        header.set_size(self.remaining);
+        let offset = format!("{:011o}\0", self.size - self.remaining);
+        header.as_mut_bytes()[369..381].copy_from_slice(offset.as_bytes());
+        header.set_cksum();
+        blocks.extend_from_slice(header.as_bytes());
+        Some(blocks)
+    }
+}
+
+fn trim_name(field: &[u8]) -> Vec<u8> {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    field[..end].to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("10", 1024), Some(10 * 1024));
+        assert_eq!(parse_size("10", 1), Some(10));
+        assert_eq!(parse_size("3M", 1024), Some(3 << 20));
+        assert_eq!(parse_size("7c", 1024), Some(7));
+        assert_eq!(parse_size("10x", 1024), None);
+        assert_eq!(parse_size("", 1024), None);
+    }
+
+    #[test]
+    fn test_continuation_header() {
+        let mut header = Header::new_gnu();
+        header.set_path("file").unwrap();
+        header.set_size(1500);
+        header.set_cksum();
+
+        let mut stream = StreamPosition::default();
+        assert!(stream.continuation_headers().is_none());
+        stream.feed(header.as_bytes());
+        stream.feed(&[0; 1024]);
+
+        let blocks = stream.continuation_headers().unwrap();
+        let continuation = Header::from_byte_slice(&blocks);
+        assert_eq!(blocks.len(), 512);
+        assert_eq!(continuation.path().unwrap().to_str(), Some("file"));
+        assert_eq!(continuation.entry_size().unwrap(), 476);
+        assert_eq!(&continuation.as_bytes()[369..381], b"00000002000\0");
+
+        stream.feed(&[0; 1024]);
+        assert!(stream.continuation_headers().is_none());
+    }
+
+    #[test]
+    fn test_continuation_header_long_name() {
+        let name = "d/".repeat(60) + "file";
+        let mut archive = tar::Builder::new(Vec::new());
+        let mut header = Header::new_gnu();
+        header.set_size(1500);
+        archive
+            .append_data(&mut header, &name, &[0u8; 1500][..])
+            .unwrap();
+        let data = archive.into_inner().unwrap();
+
+        // Break the volume after the first data block.
+        let mut stream = StreamPosition::default();
+        stream.feed(&data[..4 * 512]);
+        let blocks = stream.continuation_headers().unwrap();
+        assert_eq!(blocks.len(), 3 * 512);
+        assert_eq!(blocks[156], b'L');
+        assert_eq!(
+            &blocks[512..512 + name.len() + 1],
+            format!("{name}\0").as_bytes()
+        );
+        let continuation = Header::from_byte_slice(&blocks[2 * 512..]);
+        assert_eq!(continuation.entry_type().as_byte(), b'M');
+        assert_eq!(&continuation.as_old().name[..], &name.as_bytes()[..100]);
+        assert_eq!(continuation.entry_size().unwrap(), 988);
+    }
+}
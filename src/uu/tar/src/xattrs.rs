@@ -0,0 +1,141 @@
+// This file is part of the uutils tar package.
+//
+// For the full copyright and license information, please view the LICENSE
+// file that was distributed with this source code.
+
+//! Extended attribute support (`--xattrs`).
+//!
+//! Attributes are stored in PAX extended headers as `SCHILY.xattr.NAME`
+//! records, the encoding shared by GNU tar, star and libarchive.
+
+use crate::member::Member;
+use glob::Pattern;
+use std::ffi::OsString;
+use std::io;
+use std::path::Path;
+
+/// PAX keyword prefix under which extended attributes are stored
+pub const XATTR_PAX_PREFIX: &str = "SCHILY.xattr.";
+
+/// Attribute holding the capabilities of an executable
+const CAPABILITY_XATTR: &str = "security.capability";
+
+/// Selects which extended attributes are archived or restored
+/// (`--xattrs-include` and `--xattrs-exclude`)
+#[derive(Debug, Clone, Default)]
+pub struct XattrFilter {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+impl XattrFilter {
+    /// Builds a filter from glob patterns.
+    pub fn new<S: AsRef<str>>(include: &[S], exclude: &[S]) -> Result<Self, glob::PatternError> {
+        let compile = |patterns: &[S]| {
+            patterns
+                .iter()
+                .map(|p| Pattern::new(p.as_ref()))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(Self {
+            include: compile(include)?,
+            exclude: compile(exclude)?,
+        })
+    }
+
+    /// Returns whether the attribute `name` passes the filter.
+    ///
+    /// When no include pattern is given every attribute is archived. On
+    /// extraction GNU tar restores only the `user.*` namespace, to which
+    /// `security.capability` is added so that file capabilities survive a
+    /// round trip.
+    pub fn matches(&self, name: &str, archiving: bool) -> bool {
+        let included = if self.include.is_empty() {
+            archiving || name.starts_with("user.") || name == CAPABILITY_XATTR
+        } else {
+            self.include.iter().any(|p| p.matches(name))
+        };
+        included && !self.exclude.iter().any(|p| p.matches(name))
+    }
+}
+
+/// Reads the extended attributes of `path` that pass `filter`, returning
+/// them as PAX records sorted by keyword.
+///
+/// Symbolic links are followed, like the rest of archive creation. A
+/// filesystem without xattr support simply yields no records. ACLs are left
+/// to [`crate::acls`].
+#[cfg(unix)]
+pub fn read_pax_records(path: &Path, filter: &XattrFilter) -> io::Result<Vec<(String, Vec<u8>)>> {
+    let names = match xattr::list_deref(path) {
+        Ok(names) => names,
+        Err(e) if e.kind() == io::ErrorKind::Unsupported => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut records = Vec::new();
+    for name in names {
+        // PAX keywords are UTF-8; attributes with other names are skipped.
+        let Some(name) = name.to_str() else {
+            continue;
+        };
+        if !filter.matches(name, true) || crate::acls::ACL_XATTRS.contains(&name) {
+            continue;
+        }
+        if let Some(value) = xattr::get_deref(path, name)? {
+            records.push((format!("{XATTR_PAX_PREFIX}{}", encode_name(name)), value));
+        }
+    }
+    records.sort();
+    Ok(records)
+}
+
+#[cfg(not(unix))]
+pub fn read_pax_records(_path: &Path, _filter: &XattrFilter) -> io::Result<Vec<(String, Vec<u8>)>> {
+    Ok(Vec::new())
+}
+
+/// Collects the attributes recorded for `member` that pass `filter`.
+pub fn archived_xattrs(member: &Member, filter: &XattrFilter) -> Vec<(OsString, Vec<u8>)> {
+    member
+        .records
+        .iter()
+        .filter_map(|(keyword, value)| {
+            let name = decode_name(keyword.strip_prefix(XATTR_PAX_PREFIX)?);
+            filter
+                .matches(&name, false)
+                .then(|| (OsString::from(name), value.clone()))
+        })
+        .collect()
+}
+
+/// Sets `xattrs` on the extracted member at `path`.
+///
+/// Failures are reported as warnings, as GNU tar does, since they usually
+/// mean the filesystem or the user's privileges do not allow the attribute.
+#[cfg(unix)]
+pub fn restore_xattrs(path: &Path, xattrs: &[(OsString, Vec<u8>)]) {
+    for (name, value) in xattrs {
+        if let Err(e) = xattr::set(path, name, value) {
+            uucore::show_warning!(
+                "{}: Cannot set extended attribute {}: {e}",
+                path.display(),
+                name.to_string_lossy()
+            );
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub fn restore_xattrs(_path: &Path, _xattrs: &[(OsString, Vec<u8>)]) {}
+
+/// Escapes `%` and `=` in an attribute name, which would otherwise be
+/// ambiguous in a PAX keyword.
+fn encode_name(name: &str) -> String {
+    name.replace('%', "%25").replace('=', "%3D")
+}
+
+/// Reverses [`encode_name`].
+fn decode_name(keyword: &str) -> String {
+    keyword.replace("%3D", "=").replace("%25", "%")
+}
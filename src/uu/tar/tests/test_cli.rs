@@ -3,6 +3,8 @@
 // For the full copyright and license information, please view the LICENSE
 // file that was distributed with this source code.
 
+use std::path::PathBuf;
+
 use uu_tar::uu_app;
 
 #[test]
@@ -29,6 +31,216 @@ fn test_verbose_flag_parsing() {
     let result = app.try_get_matches_from(vec!["tar", "-cvf", "archive.tar", "file.txt"]);
     assert!(result.is_ok());
     let matches = result.unwrap();
-    assert!(matches.get_flag("verbose"));
+    assert_eq!(matches.get_count("verbose"), 1);
     assert!(matches.get_flag("create"));
+
+    let matches = uu_app()
+        .try_get_matches_from(vec!["tar", "-cvvf", "archive.tar", "file.txt"])
+        .unwrap();
+    assert_eq!(matches.get_count("verbose"), 2);
+}
+
+#[test]
+fn test_sort_option_parsing() {
+    let app = uu_app();
+    let result = app.try_get_matches_from(vec!["tar", "-cf", "a.tar", "--sort=name", "file.txt"]);
+    assert!(result.is_ok());
+    let matches = result.unwrap();
+    assert_eq!(
+        matches.get_one::<String>("sort").map(String::as_str),
+        Some("name")
+    );
+
+    let result = uu_app().try_get_matches_from(vec!["tar", "-cf", "a.tar", "--sort=size"]);
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_touch_flag_parsing() {
+    let app = uu_app();
+    let result = app.try_get_matches_from(vec!["tar", "-xmf", "archive.tar"]);
+    assert!(result.is_ok());
+    let matches = result.unwrap();
+    assert!(matches.get_flag("touch"));
+    assert!(matches.get_flag("extract"));
+}
+
+#[test]
+fn test_xattrs_patterns_accumulate() {
+    let app = uu_app();
+    let result = app.try_get_matches_from(vec![
+        "tar",
+        "-cf",
+        "archive.tar",
+        "--xattrs-include=user.*",
+        "--xattrs-include=security.capability",
+        "file.txt",
+    ]);
+    assert!(result.is_ok());
+    let matches = result.unwrap();
+    let patterns: Vec<&String> = matches.get_many("xattrs-include").unwrap().collect();
+    assert_eq!(patterns, ["user.*", "security.capability"]);
+}
+
+#[test]
+fn test_no_acls_overrides_acls() {
+    let app = uu_app();
+    let result = app.try_get_matches_from(vec![
+        "tar",
+        "-cf",
+        "archive.tar",
+        "--acls",
+        "--no-acls",
+        "f",
+    ]);
+    assert!(result.is_ok());
+    let matches = result.unwrap();
+    assert!(!matches.get_flag("acls"));
+    assert!(matches.get_flag("no-acls"));
+}
+
+#[test]
+fn test_selinux_flag_parsing() {
+    let app = uu_app();
+    let result = app.try_get_matches_from(vec!["tar", "-xf", "archive.tar", "--selinux"]);
+    assert!(result.is_ok());
+    let matches = result.unwrap();
+    assert!(matches.get_flag("selinux"));
+    assert!(!matches.get_flag("no-selinux"));
+}
+
+#[test]
+fn test_format_option_parsing() {
+    let app = uu_app();
+    let result = app.try_get_matches_from(vec!["tar", "-cf", "a.tar", "-H", "pax", "f"]);
+    assert!(result.is_ok());
+    let matches = result.unwrap();
+    assert_eq!(matches.get_one::<String>("format").unwrap(), "pax");
+
+    let result = uu_app().try_get_matches_from(vec!["tar", "-cf", "a.tar", "--format=zip", "f"]);
+    assert!(result.is_err());
+
+    let result = uu_app().try_get_matches_from(vec!["tar", "-cf", "a.tar", "--portability", "f"]);
+    assert!(result.unwrap().get_flag("old-archive"));
+}
+
+#[test]
+fn test_pax_option_accumulates() {
+    let app = uu_app();
+    let result = app.try_get_matches_from(vec![
+        "tar",
+        "-cf",
+        "a.tar",
+        "--pax-option=delete=atime",
+        "--pax-option",
+        "exthdr.name=%f",
+        "f",
+    ]);
+    assert!(result.is_ok());
+    let matches = result.unwrap();
+    let options: Vec<&String> = matches.get_many("pax-option").unwrap().collect();
+    assert_eq!(options, ["delete=atime", "exthdr.name=%f"]);
+}
+
+#[test]
+fn test_sparse_options_parsing() {
+    let app = uu_app();
+    let result = app.try_get_matches_from(vec!["tar", "-cSf", "a.tar", "f"]);
+    assert!(result.unwrap().get_flag("sparse"));
+
+    let result =
+        uu_app().try_get_matches_from(vec!["tar", "-cf", "a.tar", "--sparse-version=0.1", "f"]);
+    let matches = result.unwrap();
+    assert_eq!(matches.get_one::<String>("sparse-version").unwrap(), "0.1");
+    assert!(!matches.get_flag("sparse"));
+}
+
+#[test]
+fn test_multi_volume_options_parsing() {
+    let app = uu_app();
+    let result = app.try_get_matches_from(vec![
+        "tar",
+        "-cM",
+        "-L",
+        "100",
+        "-f",
+        "v1.tar",
+        "-f",
+        "v2.tar",
+        "--new-volume-script=next.sh",
+        "--volno-file=volno",
+        "f",
+    ]);
+    let matches = result.unwrap();
+    assert!(matches.get_flag("multi-volume"));
+    assert_eq!(matches.get_one::<String>("tape-length").unwrap(), "100");
+    assert_eq!(matches.get_many::<PathBuf>("file").unwrap().count(), 2);
+    assert_eq!(matches.get_one::<String>("info-script").unwrap(), "next.sh");
+    assert_eq!(
+        matches.get_one::<PathBuf>("volno-file").unwrap(),
+        &PathBuf::from("volno")
+    );
+}
+
+#[test]
+fn test_blocking_options_parsing() {
+    let app = uu_app();
+    let result = app.try_get_matches_from(vec!["tar", "-xB", "-b", "128", "-f", "a.tar"]);
+    let matches = result.unwrap();
+    assert!(matches.get_flag("read-full-records"));
+    assert_eq!(matches.get_one::<String>("blocking-factor").unwrap(), "128");
+
+    // The later of -b and --record-size wins.
+    let result = uu_app().try_get_matches_from(vec![
+        "tar",
+        "-xf",
+        "a.tar",
+        "-b",
+        "128",
+        "--record-size=1024",
+    ]);
+    let matches = result.unwrap();
+    assert!(matches.get_one::<String>("blocking-factor").is_none());
+    assert_eq!(matches.get_one::<String>("record-size").unwrap(), "1024");
+}
+
+#[test]
+fn test_label_option_parsing() {
+    let app = uu_app();
+    let result = app.try_get_matches_from(vec!["tar", "-cV", "backup", "-f", "a.tar", "f"]);
+    assert_eq!(
+        result.unwrap().get_one::<String>("label").unwrap(),
+        "backup"
+    );
+
+    let result = uu_app().try_get_matches_from(vec!["tar", "--version"]);
+    assert_eq!(
+        result.unwrap_err().kind(),
+        clap::error::ErrorKind::DisplayVersion
+    );
+}
+
+#[test]
+fn test_ignore_zeros_option_parsing() {
+    let matches = uu_app()
+        .try_get_matches_from(vec!["tar", "-tif", "a.tar"])
+        .unwrap();
+    assert!(matches.get_flag("ignore-zeros"));
+    assert_eq!(matches.get_one::<PathBuf>("file").unwrap(), "a.tar");
+
+    let matches = uu_app()
+        .try_get_matches_from(vec!["tar", "-tf", "a.tar"])
+        .unwrap();
+    assert!(!matches.get_flag("ignore-zeros"));
+}
+
+#[test]
+fn test_interactive_option_parsing() {
+    for args in [
+        vec!["tar", "-cwf", "a.tar", "file"],
+        vec!["tar", "-cf", "a.tar", "--confirmation", "file"],
+    ] {
+        let matches = uu_app().try_get_matches_from(args).unwrap();
+        assert!(matches.get_flag("interactive"));
+    }
 }
@@ -6,6 +6,7 @@
 use std::io;
 use std::path::PathBuf;
 use uu_tar::errors::TarError;
+use uu_tar::format::ArchiveFormat;
 
 #[test]
 fn test_tar_error_display() {
@@ -24,6 +25,43 @@ fn test_tar_error_display() {
         err.to_string(),
         "tar: /root/file: Cannot open: Permission denied"
     );
+
+    let err = TarError::FileExists {
+        path: PathBuf::from("config.ini"),
+    };
+    assert_eq!(err.to_string(), "tar: config.ini: Cannot open: File exists");
+}
+
+#[test]
+fn test_format_limit_error_display() {
+    let err = TarError::FileNameTooLong {
+        path: PathBuf::from("long/name"),
+        max: 99,
+    };
+    assert_eq!(
+        err.to_string(),
+        "tar: long/name: file name is too long (max 99)"
+    );
+
+    let err = TarError::ValueOutOfRange {
+        path: PathBuf::from("file.txt"),
+        kind: "uid_t",
+        value: 3_000_000,
+        max: 0o7777777,
+    };
+    assert_eq!(
+        err.to_string(),
+        "tar: file.txt: value 3000000 out of uid_t range 0..2097151"
+    );
+
+    let err = TarError::UnsupportedFileType {
+        path: PathBuf::from("pipe"),
+        format: ArchiveFormat::V7,
+    };
+    assert_eq!(
+        err.to_string(),
+        "tar: pipe: file type not supported by the v7 format"
+    );
 }
 
 #[test]
@@ -4,6 +4,7 @@
 // file that was distributed with this source code.
 
 use std::path::{self, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use uutests::{at_and_ucmd, new_ucmd};
 
@@ -222,136 +223,2060 @@ fn test_create_absolute_path() {
         .stdout_contains(expected_trimmed_path);
 }
 
+#[test]
+fn test_create_sort_name() {
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    at.mkdir("dir");
+    at.write("dir/c.txt", "c");
+    at.write("dir/a.txt", "a");
+    at.mkdir("dir/b");
+    at.write("dir/b/inner.txt", "inner");
+
+    ucmd.args(&["-cf", "archive.tar", "--sort=name", "dir"])
+        .succeeds()
+        .no_output();
+
+    new_ucmd!()
+        .args(&["-tf", "archive.tar"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .stdout_is("dir\ndir/a.txt\ndir/b\ndir/b/inner.txt\ndir/c.txt\n");
+}
+
+#[test]
+fn test_create_sort_name_verbose_matches_archive_order() {
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    at.mkdir("dir");
+    at.write("dir/b.txt", "b");
+    at.write("dir/a.txt", "a");
+
+    ucmd.args(&["-cvf", "archive.tar", "--sort=name", "dir"])
+        .succeeds()
+        .stdout_is(format!(
+            "dir{sep}\ndir{sep}a.txt\ndir{sep}b.txt\n",
+            sep = path::MAIN_SEPARATOR
+        ));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_create_sort_inode() {
+    use std::os::unix::fs::MetadataExt;
+
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    at.mkdir("dir");
+    let mut names = vec!["e.txt", "a.txt", "d.txt", "b.txt", "c.txt"];
+    for name in &names {
+        at.write(&format!("dir/{name}"), name);
+    }
+    names.sort_by_key(|name| at.metadata(&format!("dir/{name}")).ino());
+
+    ucmd.args(&["-cf", "archive.tar", "--sort=inode", "dir"])
+        .succeeds()
+        .no_output();
+
+    let expected: String = names.iter().map(|name| format!("dir/{name}\n")).collect();
+    new_ucmd!()
+        .args(&["-tf", "archive.tar"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .stdout_is(format!("dir\n{expected}"));
+}
+
+#[test]
+fn test_create_sort_invalid_value() {
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    at.write("file.txt", "content");
+
+    ucmd.args(&["-cf", "archive.tar", "--sort=size", "file.txt"])
+        .fails()
+        .code_is(2)
+        .stderr_contains("invalid value 'size'");
+}
+
+/// Builds the same small tree in a fresh scenario and archives it with
+/// `--reproducible`, returning the archive bytes.
+fn create_reproducible_archive(mode: u32) -> Vec<u8> {
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    at.mkdir("tree");
+    at.write("tree/b.txt", "bravo");
+    at.write("tree/a.txt", "alpha");
+    at.mkdir("tree/sub");
+    at.write("tree/sub/c.txt", "charlie");
+    #[cfg(unix)]
+    at.set_mode("tree/a.txt", mode);
+    #[cfg(not(unix))]
+    let _ = mode;
+
+    ucmd.args(&["-cf", "archive.tar", "--reproducible", "tree"])
+        .env("SOURCE_DATE_EPOCH", "1000000")
+        .succeeds()
+        .no_output();
+
+    at.read_bytes("archive.tar")
+}
+
+#[test]
+fn test_create_reproducible_is_bit_identical() {
+    let first = create_reproducible_archive(0o600);
+    let second = create_reproducible_archive(0o640);
+
+    assert!(first == second, "archives of identical trees differ");
+}
+
+#[test]
+fn test_create_reproducible_clamps_mtime() {
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    at.write("file.txt", "content");
+
+    ucmd.args(&["-cf", "archive.tar", "--reproducible", "file.txt"])
+        .env("SOURCE_DATE_EPOCH", "1000000")
+        .succeeds();
+
+    new_ucmd!()
+        .args(&["-tvf", "archive.tar"])
+        .env("TZ", "UTC")
+        .current_dir(at.as_string())
+        .succeeds()
+        .stdout_contains("1970-01-12")
+        .stdout_contains("file.txt");
+}
+
+#[test]
+fn test_create_reproducible_invalid_source_date_epoch() {
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    at.write("file.txt", "content");
+
+    ucmd.args(&["-cf", "archive.tar", "--reproducible", "file.txt"])
+        .env("SOURCE_DATE_EPOCH", "yesterday")
+        .fails()
+        .code_is(2)
+        .stderr_contains("SOURCE_DATE_EPOCH");
+}
+
+#[test]
+#[cfg(unix)]
+fn test_create_reproducible_headers_ignore_process_and_clock() {
+    let (at, _) = at_and_ucmd!();
+    at.mkdir("tmp1");
+    at.mkdir("tmp2");
+    at.mkdir("tree");
+    at.write("tree/file.txt", "content");
+    write_sparse_file(&at, "tree/disk.img");
+
+    // Labels, global records and 1.0 sparse members all add headers of
+    // tar's own. Without SOURCE_DATE_EPOCH they are dated the epoch.
+    let configs: [&[&str]; 2] = [
+        &[
+            "--format=posix",
+            "--sparse-version=1.0",
+            "--pax-option=comment=built",
+        ],
+        &["--format=gnu", "-S"],
+    ];
+    for (round, tmpdir) in ["tmp1", "tmp2"].into_iter().enumerate() {
+        if round > 0 {
+            std::thread::sleep(Duration::from_millis(1100));
+        }
+        for (index, config) in configs.iter().enumerate() {
+            new_ucmd!()
+                .args(&["-cf", &format!("{index}-{round}.tar"), "--reproducible"])
+                .args(&["-V", "Backup"])
+                .args(config)
+                .arg("tree")
+                .env("TMPDIR", at.plus_as_string(tmpdir))
+                .env("SOURCE_DATE_EPOCH", "")
+                .current_dir(at.as_string())
+                .succeeds()
+                .no_output();
+        }
+    }
+
+    for (index, config) in configs.iter().enumerate() {
+        assert!(
+            at.read_bytes(&format!("{index}-0.tar")) == at.read_bytes(&format!("{index}-1.tar")),
+            "archives made with {config:?} differ"
+        );
+    }
+}
+
+#[test]
+fn test_create_newer_mtime() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    at.mkdir("dir");
+    at.write("dir/old.txt", "old");
+    at.write("dir/new.txt", "new");
+    at.write("reference", "");
+    set_mtime(&at, "dir/old.txt", 1_000_000_000);
+    set_mtime(&at, "reference", 1_200_000_000);
+
+    // Directories are always archived.
+    ucmd.args(&[
+        "-cvf",
+        "archive.tar",
+        "--newer-mtime=2005-01-01T00:00:00Z",
+        "dir",
+    ])
+    .succeeds()
+    .stderr_contains("tar: dir/old.txt: file is unchanged; not dumped\n");
+    new_ucmd!()
+        .args(&["-tf", "archive.tar"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .stdout_only("dir\ndir/new.txt\n");
+
+    new_ucmd!()
+        .args(&["-cf", "archive.tar", "--newer-mtime", "@1100000000", "dir"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .no_output();
+    new_ucmd!()
+        .args(&["-tf", "archive.tar"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .stdout_only("dir\ndir/new.txt\n");
+
+    // A reference file stands for its modification time.
+    let reference = at.plus_as_string("reference");
+    new_ucmd!()
+        .args(&["-cf", "archive.tar", "--newer-mtime", &reference, "dir"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .no_output();
+    new_ucmd!()
+        .args(&["-tf", "archive.tar"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .stdout_only("dir\ndir/new.txt\n");
+}
+
+#[test]
+fn test_create_newer_checks_status_change() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    at.write("file.txt", "content");
+    set_mtime(&at, "file.txt", 1_000_000_000);
+
+    // The file's status changed just now, after the date.
+    ucmd.args(&["-cf", "archive.tar", "-N", "2005-01-01", "file.txt"])
+        .succeeds()
+        .no_output();
+    new_ucmd!()
+        .args(&["-tf", "archive.tar"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .stdout_only("file.txt\n");
+
+    new_ucmd!()
+        .args(&["-cf", "archive.tar", "--after-date=@4000000000", "file.txt"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .no_output();
+    new_ucmd!()
+        .args(&["-tf", "archive.tar"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .no_output();
+}
+
+#[test]
+fn test_create_newer_invalid_date() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    at.write("file.txt", "content");
+
+    ucmd.args(&["-cf", "archive.tar", "--newer=./missing", "file.txt"])
+        .fails()
+        .code_is(2)
+        .stderr_contains("Date sample file not found");
+
+    // Unknown dates are replaced by the oldest one, so everything is kept.
+    new_ucmd!()
+        .args(&["-cf", "archive.tar", "--newer=someday", "file.txt"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .stderr_contains("for unknown date format 'someday'");
+    new_ucmd!()
+        .args(&["-tf", "archive.tar"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .stdout_only("file.txt\n");
+
+    new_ucmd!()
+        .args(&[
+            "-cf",
+            "archive.tar",
+            "-N",
+            "@1",
+            "--newer-mtime=@1",
+            "file.txt",
+        ])
+        .current_dir(at.as_string())
+        .fails()
+        .stderr_contains("cannot be used with");
+}
+
+#[test]
+fn test_create_listed_incremental() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    at.mkdir("dir");
+    at.mkdir("dir/sub");
+    at.write("dir/kept.txt", "kept");
+    at.write("dir/sub/gone.txt", "gone");
+
+    // Directories come first, then the files to dump from each.
+    ucmd.args(&["-cvf", "level0.tar", "-g", "snapshot", "dir"])
+        .succeeds()
+        .stdout_is("dir/\ndir/sub/\ndir/kept.txt\ndir/sub/gone.txt\n")
+        .stderr_is("tar: dir: Directory is new\ntar: dir/sub: Directory is new\n");
+    let snapshot = at.read_bytes("snapshot");
+    assert!(snapshot.starts_with(b"GNU tar-"));
+    assert!(
+        snapshot
+            .windows(b"dir/sub\0Ygone.txt\0".len())
+            .any(|w| w == b"dir/sub\0Ygone.txt\0")
+    );
+
+    // Only what changed since the snapshot is dumped again.
+    at.write("dir/added.txt", "added");
+    set_mtime(&at, "dir/added.txt", 4_000_000_000);
+    at.remove("dir/sub/gone.txt");
+    new_ucmd!()
+        .args(&["-cf", "level1.tar", "-g", "snapshot", "dir"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .no_output();
+    new_ucmd!()
+        .args(&["-tf", "level1.tar"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .stdout_only("dir/\ndir/sub/\ndir/added.txt\n");
+    let archive = at.read_bytes("level1.tar");
+    let dumpdir = b"Yadded.txt\0Nkept.txt\0Dsub\0\0";
+    assert!(archive.windows(dumpdir.len()).any(|w| w == dumpdir));
+
+    // Level 0 dumps everything, whatever the snapshot says.
+    new_ucmd!()
+        .args(&["-cf", "level0.tar", "-g", "snapshot", "--level=0", "dir"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .no_output();
+    new_ucmd!()
+        .args(&["-tf", "level0.tar"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .stdout_only("dir/\ndir/sub/\ndir/added.txt\ndir/kept.txt\n");
+}
+
+#[test]
+fn test_create_incremental_invalid_level() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    at.write("file.txt", "content");
+
+    ucmd.args(&[
+        "-cf",
+        "archive.tar",
+        "-g",
+        "snapshot",
+        "--level=one",
+        "file.txt",
+    ])
+    .fails()
+    .code_is(2)
+    .stderr_contains("Invalid incremental level value");
+
+    new_ucmd!()
+        .args(&["-cf", "archive.tar", "--level=0", "file.txt"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .stderr_is("tar: --level is meaningless without --listed-incremental\n");
+}
+
+#[test]
+fn test_extract_incremental_deletes_files() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    at.mkdir("dir");
+    at.write("dir/kept.txt", "kept");
+    at.write("dir/gone.txt", "gone");
+    ucmd.args(&["-cf", "level0.tar", "-g", "snapshot", "dir"])
+        .succeeds();
+    at.remove("dir/gone.txt");
+    new_ucmd!()
+        .args(&["-cf", "level1.tar", "-g", "snapshot", "dir"])
+        .current_dir(at.as_string())
+        .succeeds();
+
+    new_ucmd!()
+        .args(&["-xf", "level0.tar"])
+        .current_dir(at.as_string())
+        .succeeds();
+    at.mkdir("dir/extra");
+    at.write("dir/extra/file.txt", "extra");
+
+    // Without -G, dumpdirs are plain directories.
+    new_ucmd!()
+        .args(&["-xf", "level1.tar"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .no_output();
+    assert!(at.file_exists("dir/gone.txt"));
+
+    new_ucmd!()
+        .args(&["-xvGf", "level1.tar"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .stdout_contains("dir/\ntar: Deleting 'dir/extra'\ntar: Deleting 'dir/gone.txt'\n");
+    assert!(at.file_exists("dir/kept.txt"));
+    assert!(!at.file_exists("dir/gone.txt"));
+    assert!(!at.dir_exists("dir/extra"));
+}
+
+#[test]
+fn test_create_format_header_magic() {
+    let (at, _ucmd) = at_and_ucmd!();
+
+    at.write("file.txt", "content");
+
+    let cases: [(&str, &[u8]); 4] = [
+        ("v7", b"\0\0\0\0\0\0\0\0"),
+        ("gnu", b"ustar  \0"),
+        ("ustar", b"ustar\x0000"),
+        ("posix", b"ustar\x0000"),
+    ];
+    for (format, magic) in cases {
+        let archive = format!("{format}.tar");
+        new_ucmd!()
+            .args(&["-cf", &archive, &format!("--format={format}"), "file.txt"])
+            .current_dir(at.as_string())
+            .succeeds();
+        assert_eq!(&at.read_bytes(&archive)[257..265], magic, "{format}");
+
+        new_ucmd!()
+            .args(&["-tf", &archive])
+            .current_dir(at.as_string())
+            .succeeds()
+            .stdout_is("file.txt\n");
+    }
+}
+
+#[test]
+fn test_create_format_ustar_name_too_long() {
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    let dir = format!("{}/{}", "a".repeat(100), "b".repeat(100));
+    at.mkdir_all(&dir);
+    let name = format!("{dir}/{}", "c".repeat(60));
+    at.write(&name, "content");
+
+    ucmd.args(&["-cf", "archive.tar", "-H", "ustar", &name])
+        .fails()
+        .code_is(2)
+        .stderr_contains("file name is too long (max 256)");
+}
+
+#[test]
+fn test_create_format_ustar_name_cannot_be_split() {
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    let name = "x".repeat(150);
+    at.write(&name, "content");
+
+    ucmd.args(&["-cf", "archive.tar", "-H", "ustar", &name])
+        .fails()
+        .code_is(2)
+        .stderr_contains("file name is too long (cannot be split)");
+}
+
+#[test]
+fn test_create_format_v7_name_too_long() {
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    let name = "x".repeat(100);
+    at.write(&name, "content");
+
+    ucmd.args(&["-cf", "archive.tar", "--old-archive", &name])
+        .fails()
+        .code_is(2)
+        .stderr_contains("file name is too long (max 99)");
+}
+
+#[test]
+#[cfg(unix)]
+fn test_create_format_v7_rejects_fifo() {
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    at.mkfifo("pipe");
+
+    ucmd.args(&["-cf", "archive.tar", "--format=v7", "pipe"])
+        .fails()
+        .code_is(2)
+        .stderr_contains("pipe: file type not supported by the v7 format");
+}
+
+#[test]
+fn test_create_format_posix_long_name_roundtrip() {
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    let dir = format!("{}/{}", "a".repeat(150), "b".repeat(150));
+    at.mkdir_all(&dir);
+    let name = format!("{dir}/{}", "c".repeat(150));
+    at.write(&name, "long");
+
+    ucmd.args(&["-cf", "archive.tar", "--posix", &name])
+        .succeeds();
+    // The name travels in a pax extended header ahead of the member.
+    assert_eq!(at.read_bytes("archive.tar")[156], b'x');
+    at.remove(&name);
+
+    new_ucmd!()
+        .args(&["-xf", "archive.tar"])
+        .current_dir(at.as_string())
+        .succeeds();
+    assert_eq!(at.read(&name), "long");
+}
+
+#[test]
+#[cfg(unix)]
+fn test_create_format_uid_range() {
+    if !rustix::process::geteuid().is_root() {
+        eprintln!("skipping: changing file owners requires root");
+        return;
+    }
+
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    at.write("file.txt", "content");
+    std::os::unix::fs::chown(at.plus("file.txt"), Some(3_000_000), None).unwrap();
+
+    ucmd.args(&["-cf", "archive.tar", "--format=ustar", "file.txt"])
+        .fails()
+        .code_is(2)
+        .stderr_contains("value 3000000 out of uid_t range 0..2097151");
+
+    // pax moves the uid into an extended header record instead.
+    new_ucmd!()
+        .args(&["-cf", "archive.tar", "--format=pax", "file.txt"])
+        .current_dir(at.as_string())
+        .succeeds();
+    let archive = at.read_bytes("archive.tar");
+    assert!(archive.windows(13).any(|w| w == b" uid=3000000\n"));
+}
+
+#[test]
+fn test_create_format_conflicting_aliases() {
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    at.write("file.txt", "content");
+
+    ucmd.args(&["-cf", "archive.tar", "--old-archive", "--posix", "file.txt"])
+        .fails()
+        .code_is(2);
+}
+
+#[test]
+fn test_create_xattrs_require_posix_format() {
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    at.write("file.txt", "content");
+
+    ucmd.args(&["-cf", "archive.tar", "--xattrs", "--format=gnu", "file.txt"])
+        .fails()
+        .code_is(2)
+        .stderr_contains("--xattrs can be used only on POSIX archives");
+}
+
+#[test]
+fn test_create_pax_option_records() {
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    at.write("file.txt", "content");
+
+    ucmd.args(&[
+        "-cf",
+        "archive.tar",
+        "--pax-option=COMPANY.build=42,COMPANY.note:=hello",
+        "--pax-option=COMPANY.secret:=x,delete=COMPANY.sec*",
+        "file.txt",
+    ])
+    .succeeds();
+
+    let archive = at.read_bytes("archive.tar");
+    let contains = |needle: &[u8]| archive.windows(needle.len()).any(|w| w == needle);
+    // keyword=value goes in a global header, keyword:=value in the member's.
+    assert_eq!(archive[156], b'g');
+    assert!(contains(b" COMPANY.build=42\n"));
+    assert!(contains(b" COMPANY.note=hello\n"));
+    assert!(!contains(b"COMPANY.secret"));
+
+    new_ucmd!()
+        .args(&["-tf", "archive.tar"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .stdout_is("file.txt\n");
+}
+
+#[test]
+fn test_create_pax_option_header_name_and_mtime() {
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    at.mkdir("dir");
+    at.write("dir/file.txt", "content");
+
+    ucmd.args(&[
+        "-cf",
+        "archive.tar",
+        "--pax-option=exthdr.name=%d/meta/%f,exthdr.mtime=@5,note:=x",
+        "dir/file.txt",
+    ])
+    .succeeds();
+
+    let archive = at.read_bytes("archive.tar");
+    assert!(archive.starts_with(b"dir/meta/file.txt\0"));
+    assert_eq!(&archive[136..148], b"00000000005\0");
+    assert_eq!(archive[156], b'x');
+}
+
+#[test]
+fn test_create_pax_option_delete_removes_generated_records() {
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    let dir = format!("{}/{}", "a".repeat(100), "b".repeat(100));
+    at.mkdir_all(&dir);
+    let name = format!("{dir}/file.txt");
+    at.write(&name, "content");
+
+    ucmd.args(&["-cf", "archive.tar", "--pax-option=delete=path", &name])
+        .fails()
+        .code_is(2)
+        .stderr_contains("file name is too long (cannot be split)");
+}
+
+#[test]
+fn test_create_pax_option_requires_posix_format() {
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    at.write("file.txt", "content");
+
+    ucmd.args(&[
+        "-cf",
+        "archive.tar",
+        "-H",
+        "ustar",
+        "--pax-option=a=b",
+        "file.txt",
+    ])
+    .fails()
+    .code_is(2)
+    .stderr_contains("--pax-option can be used only on POSIX archives");
+}
+
+#[test]
+fn test_create_pax_option_malformed() {
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    at.write("file.txt", "content");
+
+    ucmd.args(&["-cf", "archive.tar", "--pax-option=atime", "file.txt"])
+        .fails()
+        .code_is(2)
+        .stderr_contains("Malformed pax option: 'atime'");
+}
+
+/// Writes a 4 MiB file whose only data is 5 bytes at 1 MiB, returning
+/// whether the filesystem stored it with holes.
+#[cfg(unix)]
+fn write_sparse_file(at: &uutests::util::AtPath, name: &str) -> bool {
+    use std::io::{Seek, SeekFrom, Write};
+    use std::os::unix::fs::MetadataExt;
+
+    let mut file = std::fs::File::create(at.plus(name)).unwrap();
+    file.set_len(4 << 20).unwrap();
+    file.seek(SeekFrom::Start(1 << 20)).unwrap();
+    file.write_all(b"hello").unwrap();
+    at.metadata(name).blocks() * 512 < 4 << 20
+}
+
+#[test]
+#[cfg(unix)]
+fn test_create_sparse_roundtrip() {
+    for format in [
+        "--format=gnu",
+        "--format=oldgnu",
+        "--sparse-version=0.0",
+        "--sparse-version=0.1",
+        "--sparse-version=1.0",
+    ] {
+        let (at, mut ucmd) = at_and_ucmd!();
+        let has_holes = write_sparse_file(&at, "disk.img");
+
+        // The POSIX encodings are selected by --sparse-version alone.
+        let format_args: &[&str] = if format.starts_with("--sparse") {
+            &["--format=posix", format]
+        } else {
+            &["-S", format]
+        };
+        ucmd.args(&["-cf", "archive.tar"])
+            .args(format_args)
+            .arg("disk.img")
+            .succeeds();
+        if has_holes {
+            assert!(at.metadata("archive.tar").len() < 64 * 1024, "{format}");
+        }
+
+        new_ucmd!()
+            .args(&["-tvf", "archive.tar"])
+            .current_dir(at.as_string())
+            .succeeds()
+            .stdout_contains(" 4194304 ")
+            .stdout_contains("disk.img")
+            .stdout_does_not_contain("GNUSparseFile");
+
+        at.mkdir("out");
+        new_ucmd!()
+            .args(&["-xf", "../archive.tar"])
+            .current_dir(at.plus("out"))
+            .succeeds();
+        assert_eq!(
+            at.read_bytes("out/disk.img"),
+            at.read_bytes("disk.img"),
+            "{format}"
+        );
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn test_create_without_sparse_stores_zeros() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    write_sparse_file(&at, "disk.img");
+
+    ucmd.args(&["-cf", "archive.tar", "disk.img"]).succeeds();
+    assert!(at.metadata("archive.tar").len() > 4 << 20);
+}
+
+#[test]
+fn test_create_sparse_version_invalid() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    at.write("file.txt", "content");
+
+    ucmd.args(&["-cf", "archive.tar", "--sparse-version=2.0", "file.txt"])
+        .fails()
+        .code_is(2)
+        .stderr_contains("Invalid sparse version value: '2.0'");
+}
+
+#[test]
+fn test_create_pads_to_record_size() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    at.write("file.txt", "content");
+
+    // GNU tar's default of 20 blocks per record.
+    ucmd.args(&["-cf", "default.tar", "file.txt"]).succeeds();
+    assert_eq!(at.metadata("default.tar").len(), 10240);
+
+    new_ucmd!()
+        .args(&["-cf", "b128.tar", "-b", "128", "file.txt"])
+        .current_dir(at.as_string())
+        .succeeds();
+    assert_eq!(at.metadata("b128.tar").len(), 128 * 512);
+
+    new_ucmd!()
+        .args(&["-cf", "record.tar", "--record-size=1k", "file.txt"])
+        .current_dir(at.as_string())
+        .succeeds();
+    assert_eq!(at.metadata("record.tar").len(), 2048);
+
+    new_ucmd!()
+        .args(&["-tf", "b128.tar", "-b", "1"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .stdout_only("file.txt\n");
+}
+
+#[test]
+fn test_blocking_factor_invalid() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    at.write("file.txt", "content");
+
+    ucmd.args(&["-cf", "archive.tar", "-b", "0", "file.txt"])
+        .fails()
+        .code_is(2)
+        .stderr_contains("0: Invalid blocking factor");
+
+    new_ucmd!()
+        .args(&["-cf", "archive.tar", "--record-size=1000", "file.txt"])
+        .current_dir(at.as_string())
+        .fails()
+        .code_is(2)
+        .stderr_contains("Record size must be a multiple of 512.");
+
+    new_ucmd!()
+        .args(&["-cf", "archive.tar", "--record-size=big", "file.txt"])
+        .current_dir(at.as_string())
+        .fails()
+        .code_is(2)
+        .stderr_contains("big: Invalid record size");
+}
+
+#[test]
+fn test_read_full_records_from_fifo() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    write_numbered_file(&at, "big.bin", 30_000);
+    ucmd.args(&["-cf", "archive.tar", "-b", "64", "big.bin"])
+        .succeeds();
+
+    // Feed the archive through a pipe in pieces smaller than a record.
+    at.mkfifo("pipe");
+    let archive = at.read_bytes("archive.tar");
+    let fifo = at.plus("pipe");
+    let writer = std::thread::spawn(move || {
+        use std::io::Write;
+        let mut pipe = std::fs::File::create(fifo).unwrap();
+        for chunk in archive.chunks(1000) {
+            pipe.write_all(chunk).unwrap();
+            pipe.flush().unwrap();
+        }
+    });
+
+    at.mkdir("out");
+    new_ucmd!()
+        .args(&["-xBf", "../pipe", "-b", "64"])
+        .current_dir(at.plus("out"))
+        .succeeds();
+    writer.join().unwrap();
+    assert_eq!(at.read_bytes("out/big.bin"), at.read_bytes("big.bin"));
+}
+
+#[test]
+fn test_ignore_zeros_concatenated_archives() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    at.write("one.txt", "first");
+    at.write("two.txt", "second");
+    ucmd.args(&["-cf", "one.tar", "one.txt"]).succeeds();
+    new_ucmd!()
+        .args(&["-cf", "two.tar", "two.txt"])
+        .current_dir(at.as_string())
+        .succeeds();
+    at.write_bytes("both.tar", &at.read_bytes("one.tar"));
+    at.append_bytes("both.tar", &at.read_bytes("two.tar"));
+
+    // Without -i the end of the first archive ends the listing.
+    new_ucmd!()
+        .args(&["-tf", "both.tar"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .stdout_only("one.txt\n");
+
+    new_ucmd!()
+        .args(&["-tif", "both.tar"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .stdout_only("one.txt\ntwo.txt\n");
+
+    at.mkdir("out");
+    new_ucmd!()
+        .args(&["--ignore-zeros", "-xf", "../both.tar"])
+        .current_dir(at.plus("out"))
+        .succeeds()
+        .no_stderr();
+    assert_eq!(at.read("out/one.txt"), "first");
+    assert_eq!(at.read("out/two.txt"), "second");
+}
+
+#[test]
+fn test_lone_zero_block() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    at.write("one.txt", "first");
+    at.write("two.txt", "second");
+    ucmd.args(&["-cf", "one.tar", "one.txt"]).succeeds();
+    new_ucmd!()
+        .args(&["-cf", "two.tar", "two.txt"])
+        .current_dir(at.as_string())
+        .succeeds();
+
+    // Header, data and a single zero block, then another archive.
+    let mut archive = at.read_bytes("one.tar")[..1536].to_vec();
+    archive.extend_from_slice(&at.read_bytes("two.tar"));
+    at.write_bytes("lone.tar", &archive);
+
+    new_ucmd!()
+        .args(&["-tf", "lone.tar"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .stdout_is("one.txt\n")
+        .stderr_is("tar: A lone zero block at 3\n");
+
+    new_ucmd!()
+        .args(&["-tif", "lone.tar"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .stdout_only("one.txt\ntwo.txt\n");
+
+    // A partial block of garbage after the zero block is dropped, as GNU
+    // tar does.
+    archive.truncate(1536);
+    archive.extend_from_slice(b"garbage");
+    at.write_bytes("garbage.tar", &archive);
+    new_ucmd!()
+        .args(&["-tf", "garbage.tar"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .stdout_is("one.txt\n")
+        .stderr_is("tar: A lone zero block at 3\n");
+    new_ucmd!()
+        .args(&["-tif", "garbage.tar"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .stdout_only("one.txt\n");
+}
+
+/// Writes a file of `len` bytes that does not repeat within a block, so a
+/// member spliced together at the wrong offset is caught.
+fn write_numbered_file(at: &uutests::util::AtPath, name: &str, len: usize) {
+    let data: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+    at.write_bytes(name, &data);
+}
+
+#[test]
+fn test_multi_volume_roundtrip() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    write_numbered_file(&at, "big.bin", 25_000);
+    at.write("small.txt", "small");
+
+    ucmd.args(&["-cM", "-L", "10"])
+        .args(&["-f", "v1.tar", "-f", "v2.tar", "-f", "v3.tar"])
+        .args(&["big.bin", "small.txt"])
+        .succeeds();
+
+    // Each full volume holds one record; later ones open with an `M` header
+    // for the rest of big.bin.
+    assert_eq!(at.metadata("v1.tar").len(), 10240);
+    assert_eq!(at.metadata("v2.tar").len(), 10240);
+    let second = at.read_bytes("v2.tar");
+    assert_eq!(&second[..8], b"big.bin\0");
+    assert_eq!(second[156], b'M');
+
+    new_ucmd!()
+        .args(&["-tM", "-f", "v1.tar", "-f", "v2.tar", "-f", "v3.tar"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .stdout_is("big.bin\nsmall.txt\n");
+
+    at.mkdir("out");
+    new_ucmd!()
+        .args(&[
+            "-xM",
+            "-f",
+            "../v1.tar",
+            "-f",
+            "../v2.tar",
+            "-f",
+            "../v3.tar",
+        ])
+        .current_dir(at.plus("out"))
+        .succeeds();
+    assert_eq!(at.read_bytes("out/big.bin"), at.read_bytes("big.bin"));
+    assert_eq!(at.read("out/small.txt"), "small");
+}
+
+#[test]
+fn test_multi_volume_info_script_names_volumes() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    write_numbered_file(&at, "big.bin", 12_000);
+
+    let script = "echo $TAR_SUBCOMMAND $TAR_ARCHIVE >> log; echo vol$TAR_VOLUME.tar >&$TAR_FD";
+    ucmd.args(&["-c", "-L", "10", "-F", script, "-f", "vol1.tar", "big.bin"])
+        .succeeds();
+    assert!(at.file_exists("vol2.tar"));
+    assert_eq!(at.read("log"), "-c vol1.tar\n");
+
+    at.mkdir("out");
+    new_ucmd!()
+        .args(&["-x", "--new-volume-script", "echo ../vol2.tar >&$TAR_FD"])
+        .args(&["-f", "../vol1.tar"])
+        .current_dir(at.plus("out"))
+        .succeeds();
+    assert_eq!(at.read_bytes("out/big.bin"), at.read_bytes("big.bin"));
+}
+
+#[test]
+fn test_multi_volume_info_script_failure() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    write_numbered_file(&at, "big.bin", 12_000);
+
+    ucmd.args(&[
+        "-c", "-L", "10", "-F", "exit 1", "-f", "vol1.tar", "big.bin",
+    ])
+    .fails()
+    .code_is(2)
+    .stderr_contains("'exit 1' command failed");
+}
+
+#[test]
+fn test_multi_volume_prompt() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    write_numbered_file(&at, "big.bin", 12_000);
+
+    ucmd.args(&["-cM", "-L", "10", "-f", "first.tar", "big.bin"])
+        .pipe_in("n second.tar\n")
+        .succeeds()
+        .stderr_contains("Prepare volume #2 for 'first.tar' and hit return: ");
+    assert_eq!(at.read_bytes("second.tar")[156], b'M');
+
+    new_ucmd!()
+        .args(&["-cM", "-L", "10", "-f", "first.tar", "big.bin"])
+        .current_dir(at.as_string())
+        .pipe_in("?\nq\n")
+        .fails()
+        .code_is(2)
+        .stderr_contains("Give a new file name for the next (and subsequent) volume(s)")
+        .stderr_contains("No new volume; exiting.");
+}
+
+#[test]
+fn test_multi_volume_volno_file() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    write_numbered_file(&at, "big.bin", 12_000);
+    at.write("volno", "3\n");
+
+    ucmd.args(&["-cM", "-L", "10", "--volno-file", "volno"])
+        .args(&["-f", "v1.tar", "-f", "v2.tar", "big.bin"])
+        .succeeds();
+    assert_eq!(at.read("volno"), "4\n");
+}
+
+#[test]
+fn test_multi_volume_continued_member_alone() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    write_numbered_file(&at, "big.bin", 12_000);
+    ucmd.args(&["-cM", "-L", "10", "-f", "v1.tar", "-f", "v2.tar", "big.bin"])
+        .succeeds();
+
+    new_ucmd!()
+        .args(&["-tvf", "v2.tar"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .stdout_contains("M--------- ")
+        .stdout_contains(" 2272 ")
+        .stdout_contains("big.bin--Continued at byte 9728--");
+
+    at.mkdir("out");
+    new_ucmd!()
+        .args(&["-xf", "../v2.tar"])
+        .current_dir(at.plus("out"))
+        .fails()
+        .code_is(2)
+        .stderr_contains("big.bin: Cannot extract -- file is continued from another volume");
+    assert!(!at.file_exists("out/big.bin"));
+}
+
+#[test]
+fn test_multi_volume_label_on_each_volume() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    write_numbered_file(&at, "big.bin", 12_000);
+
+    ucmd.args(&["-cM", "-L", "10", "-V", "nightly"])
+        .args(&["-f", "v1.tar", "-f", "v2.tar", "big.bin"])
+        .succeeds();
+
+    // The second volume opens with its label, then the continuation.
+    let second = at.read_bytes("v2.tar");
+    assert_eq!(&second[..17], b"nightly Volume 2\0");
+    assert_eq!(second[156], b'V');
+    assert_eq!(second[512 + 156], b'M');
+
+    at.mkdir("out");
+    new_ucmd!()
+        .args(&["-xM", "-V", "nightly", "-f", "../v1.tar", "-f", "../v2.tar"])
+        .current_dir(at.plus("out"))
+        .succeeds();
+    assert_eq!(at.read_bytes("out/big.bin"), at.read_bytes("big.bin"));
+}
+
+#[test]
+fn test_multi_volume_long_name() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    let dir = ["long-directory-name"; 6].join("/");
+    let name = format!("{dir}/big.bin");
+    assert!(name.len() > 100);
+    at.mkdir_all(&dir);
+    write_numbered_file(&at, &name, 12_000);
+
+    ucmd.args(&["-cM", "-L", "10", "-f", "v1.tar", "-f", "v2.tar"])
+        .arg(&name)
+        .succeeds();
+
+    // The name does not fit in the `M` header, so an `L` header comes first.
+    let second = at.read_bytes("v2.tar");
+    assert_eq!(second[156], b'L');
+    assert_eq!(&second[512..512 + name.len()], name.as_bytes());
+    assert_eq!(second[2 * 512 + 156], b'M');
+
+    new_ucmd!()
+        .args(&["-tvf", "v2.tar"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .stdout_contains(format!("{name}--Continued at byte"));
+
+    at.mkdir("out");
+    new_ucmd!()
+        .args(&["-xM", "-f", "../v1.tar", "-f", "../v2.tar"])
+        .current_dir(at.plus("out"))
+        .succeeds()
+        .no_stderr();
+    assert_eq!(at.read_bytes(&format!("out/{name}")), at.read_bytes(&name));
+
+    // GNU tar cuts the name short instead, which is accepted with a warning.
+    at.write_bytes("gnu2.tar", &second[2 * 512..]);
+    at.mkdir("gnu");
+    new_ucmd!()
+        .args(&["-xM", "-f", "../v1.tar", "-f", "../gnu2.tar"])
+        .current_dir(at.plus("gnu"))
+        .succeeds()
+        .stderr_is(format!(
+            "tar: '{name}' is possibly continued on this volume: header contains truncated name\n"
+        ));
+    assert_eq!(at.read_bytes(&format!("gnu/{name}")), at.read_bytes(&name));
+}
+
+#[test]
+fn test_multi_volume_requires_gnu_format() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    at.write("file.txt", "content");
+
+    ucmd.args(&["-cM", "--format=ustar", "-f", "archive.tar", "file.txt"])
+        .fails()
+        .code_is(2)
+        .stderr_contains("GNU features wanted on incompatible archive format");
+}
+
+#[test]
+fn test_multi_volume_tape_length_too_short() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    at.write("file.txt", "content");
+
+    ucmd.args(&["-c", "-L", "5", "-f", "archive.tar", "file.txt"])
+        .fails()
+        .code_is(2)
+        .stderr_contains("Volume length cannot be less than record size");
+}
+
+#[test]
+fn test_create_label() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    at.write("file.txt", "content");
+
+    ucmd.args(&["-cf", "archive.tar", "-V", "host1 2026-10-18", "file.txt"])
+        .succeeds();
+    let archive = at.read_bytes("archive.tar");
+    assert_eq!(&archive[..17], b"host1 2026-10-18\0");
+    assert_eq!(archive[156], b'V');
+
+    new_ucmd!()
+        .args(&["-tf", "archive.tar"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .stdout_only("host1 2026-10-18\nfile.txt\n");
+
+    new_ucmd!()
+        .args(&["-tvf", "archive.tar"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .stdout_contains("V--------- 0/0 ")
+        .stdout_contains(" host1 2026-10-18--Volume Header--\n");
+}
+
+#[test]
+fn test_label_checked_on_extract() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    at.write("file.txt", "content");
+    ucmd.args(&["-cf", "archive.tar", "--label=host1 2026-10-18", "file.txt"])
+        .succeeds();
+    at.remove("file.txt");
+
+    new_ucmd!()
+        .args(&["-xf", "archive.tar", "-V", "host2*"])
+        .current_dir(at.as_string())
+        .fails()
+        .code_is(2)
+        .stderr_contains("Volume 'host1 2026-10-18' does not match 'host2*'");
+    assert!(!at.file_exists("file.txt"));
+
+    new_ucmd!()
+        .args(&["-xf", "archive.tar", "-V", "host1*"])
+        .current_dir(at.as_string())
+        .succeeds();
+    assert_eq!(at.read("file.txt"), "content");
+    assert!(!at.file_exists("host1 2026-10-18"));
+}
+
+#[test]
+fn test_label_missing() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    at.write("file.txt", "content");
+    ucmd.args(&["-cf", "archive.tar", "file.txt"]).succeeds();
+
+    new_ucmd!()
+        .args(&["-tf", "archive.tar", "-V", "backup"])
+        .current_dir(at.as_string())
+        .fails()
+        .code_is(2)
+        .stderr_contains("Archive not labeled to match 'backup'");
+}
+
+#[test]
+fn test_label_posix_global_header() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    at.write("file.txt", "content");
+
+    ucmd.args(&["-cf", "archive.tar", "--format=posix", "-V", "backup"])
+        .arg("file.txt")
+        .succeeds();
+    let archive = at.read_bytes("archive.tar");
+    assert_eq!(archive[156], b'g');
+    assert!(archive.windows(23).any(|w| w == b"GNU.volume.label=backup"));
+
+    new_ucmd!()
+        .args(&["-tf", "archive.tar", "-V", "back*"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .stdout_only("backup\nfile.txt\n");
+}
+
+#[test]
+fn test_label_rejected() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    at.write("file.txt", "content");
+
+    ucmd.args(&["-cf", "archive.tar", "--format=ustar", "-V", "backup"])
+        .arg("file.txt")
+        .fails()
+        .code_is(2)
+        .stderr_contains("GNU features wanted on incompatible archive format");
+
+    let long = "x".repeat(100);
+    new_ucmd!()
+        .args(&["-cf", "archive.tar", "-V", &long, "file.txt"])
+        .current_dir(at.as_string())
+        .fails()
+        .code_is(2)
+        .stderr_contains("Volume label is too long (limit is 99 bytes)");
+}
+
 // Extract operation tests
 
 #[test]
-fn test_extract_single_file() {
+fn test_extract_single_file() {
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    // Create an archive first
+    at.write("original.txt", "test content");
+    ucmd.args(&["-cf", "archive.tar", "original.txt"])
+        .succeeds();
+
+    // Remove original and extract
+    at.remove("original.txt");
+
+    new_ucmd!()
+        .arg("-xf")
+        .arg(at.plus("archive.tar"))
+        .current_dir(at.as_string())
+        .succeeds()
+        .no_output();
+
+    assert!(at.file_exists("original.txt"));
+    assert_eq!(at.read("original.txt"), "test content");
+}
+
+#[test]
+fn test_extract_verbose() {
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    // Create an archive with multiple files
+    at.write("file1.txt", "content1");
+    at.write("file2.txt", "content2");
+    at.write("file3.txt", "content3");
+
+    ucmd.args(&["-cf", "archive.tar", "file1.txt", "file2.txt", "file3.txt"])
+        .succeeds();
+
+    at.remove("file1.txt");
+    at.remove("file2.txt");
+    at.remove("file3.txt");
+
+    // Extract with verbose
+    let result = new_ucmd!()
+        .arg("-xvf")
+        .arg(at.plus("archive.tar"))
+        .current_dir(at.as_string())
+        .succeeds();
+
+    let stdout = result.stdout_str();
+
+    // Verify all files are listed in verbose output
+    assert!(stdout.contains("file1.txt"));
+    assert!(stdout.contains("file2.txt"));
+    assert!(stdout.contains("file3.txt"));
+
+    // Verify files were extracted
+    assert!(at.file_exists("file1.txt"));
+    assert!(at.file_exists("file2.txt"));
+    assert!(at.file_exists("file3.txt"));
+}
+
+#[test]
+fn test_extract_multiple_files() {
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    // Create an archive with multiple files
+    at.write("file1.txt", "content1");
+    at.write("file2.txt", "content2");
+    ucmd.args(&["-cf", "archive.tar", "file1.txt", "file2.txt"])
+        .succeeds();
+
+    // Remove originals
+    at.remove("file1.txt");
+    at.remove("file2.txt");
+
+    // Extract
+    new_ucmd!()
+        .arg("-xf")
+        .arg(at.plus("archive.tar"))
+        .current_dir(at.as_string())
+        .succeeds();
+
+    assert!(at.file_exists("file1.txt"));
+    assert!(at.file_exists("file2.txt"));
+    assert_eq!(at.read("file1.txt"), "content1");
+    assert_eq!(at.read("file2.txt"), "content2");
+}
+
+#[test]
+fn test_extract_nonexistent_archive() {
+    new_ucmd!()
+        .args(&["-xf", "nonexistent.tar"])
+        .fails()
+        .code_is(2)
+        .stderr_contains("nonexistent.tar");
+}
+
+#[test]
+fn test_extract_directory_structure() {
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    // Create directory structure
+    at.mkdir("testdir");
+    at.write("testdir/file1.txt", "content1");
+    at.mkdir("testdir/subdir");
+    at.write("testdir/subdir/file2.txt", "content2");
+
+    // Create archive
+    ucmd.args(&["-cf", "archive.tar", "testdir"]).succeeds();
+
+    // Remove directory contents and directory itself
+    at.remove("testdir/subdir/file2.txt");
+    at.remove("testdir/file1.txt");
+    at.rmdir("testdir/subdir");
+    at.rmdir("testdir");
+
+    // Extract (extracts to current directory)
+    new_ucmd!()
+        .arg("-xf")
+        .arg(at.plus("archive.tar"))
+        .current_dir(at.as_string())
+        .succeeds();
+
+    // Verify structure
+    assert!(at.dir_exists("testdir"));
+    assert!(at.file_exists("testdir/file1.txt"));
+    assert!(at.dir_exists("testdir/subdir"));
+    assert!(at.file_exists("testdir/subdir/file2.txt"));
+    assert_eq!(at.read("testdir/file1.txt"), "content1");
+    assert_eq!(at.read("testdir/subdir/file2.txt"), "content2");
+}
+
+/// Sets the modification time of `path` (a file or directory) in the fixture.
+fn set_mtime(at: &uutests::util::AtPath, path: &str, secs: u64) {
+    let time = UNIX_EPOCH + Duration::from_secs(secs);
+    std::fs::File::open(at.plus(path))
+        .unwrap()
+        .set_modified(time)
+        .unwrap();
+}
+
+fn mtime_of(at: &uutests::util::AtPath, path: &str) -> SystemTime {
+    at.metadata(path).modified().unwrap()
+}
+
+#[test]
+fn test_extract_restores_mtime() {
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    at.write("file.txt", "content");
+    set_mtime(&at, "file.txt", 1_000_000_000);
+    ucmd.args(&["-cf", "archive.tar", "file.txt"]).succeeds();
+    at.remove("file.txt");
+
+    new_ucmd!()
+        .args(&["-xf", "archive.tar"])
+        .current_dir(at.as_string())
+        .succeeds();
+
+    assert_eq!(
+        mtime_of(&at, "file.txt"),
+        UNIX_EPOCH + Duration::from_secs(1_000_000_000)
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn test_extract_restores_directory_mtime_after_contents() {
     let (at, mut ucmd) = at_and_ucmd!();
 
-    // Create an archive first
-    at.write("original.txt", "test content");
-    ucmd.args(&["-cf", "archive.tar", "original.txt"])
+    at.mkdir("dir");
+    at.write("dir/file.txt", "content");
+    set_mtime(&at, "dir", 1_000_000_000);
+    ucmd.args(&["-cf", "archive.tar", "dir"]).succeeds();
+    at.remove("dir/file.txt");
+    at.rmdir("dir");
+
+    new_ucmd!()
+        .args(&["-xf", "archive.tar"])
+        .current_dir(at.as_string())
         .succeeds();
 
-    // Remove original and extract
-    at.remove("original.txt");
+    assert!(at.file_exists("dir/file.txt"));
+    assert_eq!(
+        mtime_of(&at, "dir"),
+        UNIX_EPOCH + Duration::from_secs(1_000_000_000)
+    );
+}
+
+#[test]
+fn test_extract_touch_keeps_current_time() {
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    at.write("file.txt", "content");
+    set_mtime(&at, "file.txt", 1_000_000_000);
+    ucmd.args(&["-cf", "archive.tar", "file.txt"]).succeeds();
+    at.remove("file.txt");
 
     new_ucmd!()
-        .arg("-xf")
-        .arg(at.plus("archive.tar"))
+        .args(&["-xmf", "archive.tar"])
         .current_dir(at.as_string())
         .succeeds()
         .no_output();
 
-    assert!(at.file_exists("original.txt"));
-    assert_eq!(at.read("original.txt"), "test content");
+    assert!(mtime_of(&at, "file.txt") > UNIX_EPOCH + Duration::from_secs(1_000_000_000));
 }
 
 #[test]
-fn test_extract_verbose() {
+fn test_extract_touch_long_option() {
     let (at, mut ucmd) = at_and_ucmd!();
 
-    // Create an archive with multiple files
-    at.write("file1.txt", "content1");
-    at.write("file2.txt", "content2");
-    at.write("file3.txt", "content3");
+    at.write("file.txt", "content");
+    set_mtime(&at, "file.txt", 1_000_000_000);
+    ucmd.args(&["-cf", "archive.tar", "file.txt"]).succeeds();
+    at.remove("file.txt");
 
-    ucmd.args(&["-cf", "archive.tar", "file1.txt", "file2.txt", "file3.txt"])
+    new_ucmd!()
+        .args(&["-x", "--touch", "-f", "archive.tar"])
+        .current_dir(at.as_string())
         .succeeds();
 
-    at.remove("file1.txt");
-    at.remove("file2.txt");
-    at.remove("file3.txt");
+    assert!(mtime_of(&at, "file.txt") > UNIX_EPOCH + Duration::from_secs(1_000_000_000));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_extract_read_only_directory_before_contents() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    at.mkdir("ro");
+    at.mkdir("ro/sub");
+    at.write("ro/sub/file.txt", "content");
+    at.set_mode("ro/sub", 0o555);
+    at.set_mode("ro", 0o555);
+    ucmd.args(&["-cf", "archive.tar", "ro"]).succeeds();
+
+    at.set_mode("ro", 0o755);
+    at.set_mode("ro/sub", 0o755);
+    at.remove("ro/sub/file.txt");
+    at.rmdir("ro/sub");
+    at.rmdir("ro");
+
+    new_ucmd!()
+        .args(&["-xf", "archive.tar"])
+        .current_dir(at.as_string())
+        .succeeds();
+
+    assert_eq!(at.read("ro/sub/file.txt"), "content");
+    assert_eq!(at.metadata("ro").permissions().mode() & 0o777, 0o555);
+    assert_eq!(at.metadata("ro/sub").permissions().mode() & 0o777, 0o555);
+
+    // Cleanup - restore permissions so test cleanup can work
+    at.set_mode("ro", 0o755);
+    at.set_mode("ro/sub", 0o755);
+}
+
+#[test]
+#[cfg(unix)]
+fn test_extract_touch_still_restores_directory_mode() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    at.mkdir("dir");
+    at.write("dir/file.txt", "content");
+    at.set_mode("dir", 0o750);
+    ucmd.args(&["-cf", "archive.tar", "dir"]).succeeds();
+    at.remove("dir/file.txt");
+    at.rmdir("dir");
+
+    new_ucmd!()
+        .args(&["-xmf", "archive.tar"])
+        .current_dir(at.as_string())
+        .succeeds();
+
+    assert!(at.file_exists("dir/file.txt"));
+    assert_eq!(at.metadata("dir").permissions().mode() & 0o777, 0o750);
+}
+
+/// Builds a GNU archive from `(name, type, link target, data)` members,
+/// writing the names as given, which the tar crate's path setters would
+/// refuse for absolute names or `..`.
+fn raw_archive(members: &[(&str, tar_rs_crate::EntryType, &str, &[u8])]) -> Vec<u8> {
+    let mut builder = tar_rs_crate::Builder::new(Vec::new());
+    for &(name, entry_type, link, data) in members {
+        let mut header = tar_rs_crate::Header::new_gnu();
+        header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+        header.as_old_mut().linkname[..link.len()].copy_from_slice(link.as_bytes());
+        header.set_entry_type(entry_type);
+        header.set_size(data.len() as u64);
+        header.set_mode(if entry_type.is_dir() { 0o755 } else { 0o644 });
+        header.set_cksum();
+        builder.append(&header, data).unwrap();
+    }
+    builder.into_inner().unwrap()
+}
+
+#[test]
+fn test_extract_strips_leading_slash() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    let regular = tar_rs_crate::EntryType::Regular;
+    at.write_bytes(
+        "archive.tar",
+        &raw_archive(&[
+            ("/abs/one.txt", regular, "", b"one"),
+            ("/abs/two.txt", regular, "", b"two"),
+        ]),
+    );
+
+    // The warning is given once per prefix.
+    ucmd.args(&["-xf", "archive.tar"])
+        .succeeds()
+        .stderr_is("tar: Removing leading `/' from member names\n");
+    assert_eq!(at.read("abs/one.txt"), "one");
+    assert_eq!(at.read("abs/two.txt"), "two");
+}
+
+#[test]
+fn test_extract_rejects_dot_dot() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    let regular = tar_rs_crate::EntryType::Regular;
+    at.mkdir("out");
+    at.write_bytes(
+        "archive.tar",
+        &raw_archive(&[
+            ("../escape.txt", regular, "", b"escaped"),
+            ("sub/../../escape2.txt", regular, "", b"escaped"),
+            ("ok.txt", regular, "", b"ok"),
+        ]),
+    );
+
+    // Rejected members get no warning about their prefix.
+    ucmd.args(&["-xf", "../archive.tar"])
+        .current_dir(at.plus("out"))
+        .fails()
+        .code_is(2)
+        .stderr_contains("tar: ../escape.txt: Member name contains '..'\n")
+        .stderr_contains("tar: sub/../../escape2.txt: Member name contains '..'\n")
+        .stderr_does_not_contain("Removing leading");
+    assert!(!at.file_exists("escape.txt"));
+    assert!(!at.file_exists("escape2.txt"));
+    assert_eq!(at.read("out/ok.txt"), "ok");
+
+    // -P extracts the names as they are.
+    new_ucmd!()
+        .args(&["-xPf", "../archive.tar"])
+        .current_dir(at.plus("out"))
+        .succeeds()
+        .no_stderr();
+    assert_eq!(at.read("escape.txt"), "escaped");
+
+    at.write_bytes(
+        "absolute.tar",
+        &raw_archive(&[("/../escape3.txt", regular, "", b"escaped")]),
+    );
+    new_ucmd!()
+        .args(&["-xf", "../absolute.tar"])
+        .current_dir(at.plus("out"))
+        .fails()
+        .code_is(2)
+        .stderr_is("tar: /../escape3.txt: Member name contains '..'\n");
+}
+
+#[test]
+#[cfg(unix)]
+fn test_extract_does_not_follow_archived_symlinks() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    let regular = tar_rs_crate::EntryType::Regular;
+    let symlink = tar_rs_crate::EntryType::Symlink;
+    at.mkdir("outside");
+    at.mkdir("out");
+    let outside = at.plus_as_string("outside");
+    at.write_bytes(
+        "archive.tar",
+        &raw_archive(&[
+            ("abs", symlink, &outside, b""),
+            ("abs/passwd", regular, "", b"pwned"),
+            ("rel", symlink, "../outside", b""),
+            ("rel/passwd", regular, "", b"pwned"),
+            ("ok.txt", regular, "", b"ok"),
+        ]),
+    );
+
+    ucmd.args(&["-xf", "../archive.tar"])
+        .current_dir(at.plus("out"))
+        .fails()
+        .code_is(2)
+        .stderr_contains("abs/passwd")
+        .stderr_contains("rel/passwd");
+    assert!(!at.file_exists("outside/passwd"));
+    assert_eq!(at.read("out/ok.txt"), "ok");
+
+    // The links themselves are created once everything else is extracted.
+    assert_eq!(
+        std::fs::read_link(at.plus("out/abs")).unwrap(),
+        PathBuf::from(&outside)
+    );
+    assert_eq!(
+        std::fs::read_link(at.plus("out/rel")).unwrap(),
+        PathBuf::from("../outside")
+    );
+
+    // With -P they are created straight away.
+    at.mkdir("out2");
+    new_ucmd!()
+        .args(&["-xPf", "../archive.tar"])
+        .current_dir(at.plus("out2"))
+        .succeeds();
+    assert_eq!(at.read("outside/passwd"), "pwned");
+}
+
+#[test]
+#[cfg(unix)]
+fn test_extract_follows_symlinks_within_archive() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    at.write_bytes(
+        "archive.tar",
+        &raw_archive(&[
+            ("dir/", tar_rs_crate::EntryType::Directory, "", b""),
+            ("link", tar_rs_crate::EntryType::Symlink, "dir", b""),
+            (
+                "link/file.txt",
+                tar_rs_crate::EntryType::Regular,
+                "",
+                b"inside",
+            ),
+        ]),
+    );
+
+    ucmd.args(&["-xf", "archive.tar"]).succeeds().no_stderr();
+    assert_eq!(at.read("dir/file.txt"), "inside");
+}
+
+#[test]
+fn test_extract_hard_link_target_stripped() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    at.write_bytes(
+        "archive.tar",
+        &raw_archive(&[
+            ("file.txt", tar_rs_crate::EntryType::Regular, "", b"content"),
+            ("link.txt", tar_rs_crate::EntryType::Link, "/file.txt", b""),
+            (
+                "missing.txt",
+                tar_rs_crate::EntryType::Link,
+                "../nothing",
+                b"",
+            ),
+        ]),
+    );
+
+    ucmd.args(&["-xf", "archive.tar"])
+        .fails()
+        .code_is(2)
+        .stderr_contains("tar: Removing leading `/' from hard link targets\n")
+        .stderr_contains("tar: Removing leading `../' from hard link targets\n")
+        .stderr_contains("tar: missing.txt: Cannot hard link to 'nothing'");
+    assert_eq!(at.read("link.txt"), "content");
+}
+
+// Extended attribute tests
+
+/// Sets a `user.*` attribute, returning false when the filesystem backing the
+/// test directory does not support extended attributes.
+#[cfg(unix)]
+fn try_set_xattr(at: &uutests::util::AtPath, path: &str, name: &str, value: &[u8]) -> bool {
+    match xattr::set(at.plus(path), name, value) {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("skipping: cannot set xattrs: {e}");
+            false
+        }
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn test_xattrs_roundtrip() {
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    at.mkdir("dir");
+    at.write("dir/file.txt", "content");
+    if !try_set_xattr(&at, "dir/file.txt", "user.comment", b"hello") {
+        return;
+    }
+    try_set_xattr(&at, "dir", "user.origin", b"build");
+
+    ucmd.args(&["-cf", "archive.tar", "--xattrs", "dir"])
+        .succeeds();
+    at.remove("dir/file.txt");
+    at.rmdir("dir");
+
+    new_ucmd!()
+        .args(&["-xf", "archive.tar", "--xattrs"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .no_output();
+
+    assert_eq!(
+        xattr::get(at.plus("dir/file.txt"), "user.comment").unwrap(),
+        Some(b"hello".to_vec())
+    );
+    assert_eq!(
+        xattr::get(at.plus("dir"), "user.origin").unwrap(),
+        Some(b"build".to_vec())
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn test_xattrs_not_restored_without_flag() {
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    at.write("file.txt", "content");
+    if !try_set_xattr(&at, "file.txt", "user.comment", b"hello") {
+        return;
+    }
+
+    ucmd.args(&["-cf", "archive.tar", "--xattrs", "file.txt"])
+        .succeeds();
+    at.remove("file.txt");
+
+    new_ucmd!()
+        .args(&["-xf", "archive.tar"])
+        .current_dir(at.as_string())
+        .succeeds();
+
+    assert!(at.file_exists("file.txt"));
+    assert_eq!(
+        xattr::get(at.plus("file.txt"), "user.comment").unwrap(),
+        None
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn test_xattrs_include_and_exclude() {
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    at.write("file.txt", "content");
+    if !try_set_xattr(&at, "file.txt", "user.keep", b"1") {
+        return;
+    }
+    try_set_xattr(&at, "file.txt", "user.skip.archive", b"2");
+    try_set_xattr(&at, "file.txt", "user.skip.extract", b"3");
+
+    ucmd.args(&[
+        "-cf",
+        "archive.tar",
+        "--xattrs-exclude=user.skip.archive",
+        "file.txt",
+    ])
+    .succeeds();
+    at.remove("file.txt");
+
+    new_ucmd!()
+        .args(&[
+            "-xf",
+            "archive.tar",
+            "--xattrs-include=user.*",
+            "--xattrs-exclude=*.extract",
+        ])
+        .current_dir(at.as_string())
+        .succeeds();
+
+    let path = at.plus("file.txt");
+    assert_eq!(xattr::get(&path, "user.keep").unwrap(), Some(b"1".to_vec()));
+    assert_eq!(xattr::get(&path, "user.skip.archive").unwrap(), None);
+    assert_eq!(xattr::get(&path, "user.skip.extract").unwrap(), None);
+}
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn test_xattrs_security_capability_roundtrip() {
+    if !rustix::process::geteuid().is_root() {
+        eprintln!("skipping: setting security.capability requires root");
+        return;
+    }
+
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    // VFS_CAP_REVISION_2 granting CAP_NET_BIND_SERVICE in the permitted set
+    let capability: Vec<u8> = [0, 0, 0, 2, 0, 4, 0, 0]
+        .into_iter()
+        .chain([0; 12])
+        .collect();
+
+    at.write("server", "binary");
+    if xattr::set(at.plus("server"), "security.capability", &capability).is_err() {
+        eprintln!("skipping: cannot set security.capability");
+        return;
+    }
+
+    ucmd.args(&["-cf", "archive.tar", "--xattrs", "server"])
+        .succeeds();
+    at.remove("server");
+
+    // Plain --xattrs restores capabilities along with user.*.
+    new_ucmd!()
+        .args(&["-xf", "archive.tar", "--xattrs"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .no_stderr();
+
+    assert_eq!(
+        xattr::get(at.plus("server"), "security.capability").unwrap(),
+        Some(capability)
+    );
+}
+
+// ACL tests
+
+/// Builds a `system.posix_acl_*` value from (tag, perm, id) entries.
+#[cfg(target_os = "linux")]
+fn posix_acl(entries: &[(u16, u16, u32)]) -> Vec<u8> {
+    let mut value = 2u32.to_le_bytes().to_vec();
+    for &(tag, perm, id) in entries {
+        value.extend_from_slice(&tag.to_le_bytes());
+        value.extend_from_slice(&perm.to_le_bytes());
+        value.extend_from_slice(&id.to_le_bytes());
+    }
+    value
+}
+
+/// `user::rw-,user:65534:r--,group::r--,mask::r--,other::---`
+#[cfg(target_os = "linux")]
+fn sample_acl() -> Vec<u8> {
+    posix_acl(&[
+        (0x01, 6, u32::MAX),
+        (0x02, 4, 65534),
+        (0x04, 4, u32::MAX),
+        (0x10, 4, u32::MAX),
+        (0x20, 0, u32::MAX),
+    ])
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_acls_roundtrip() {
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    at.mkdir("dir");
+    at.write("dir/file.txt", "content");
+    if xattr::set(
+        at.plus("dir/file.txt"),
+        "system.posix_acl_access",
+        &sample_acl(),
+    )
+    .is_err()
+        || xattr::set(at.plus("dir"), "system.posix_acl_default", &sample_acl()).is_err()
+    {
+        eprintln!("skipping: filesystem does not support ACLs");
+        return;
+    }
+
+    ucmd.args(&["-cf", "archive.tar", "--acls", "dir"])
+        .succeeds();
+    let archive = at.read_bytes("archive.tar");
+    assert!(archive.windows(17).any(|w| w == b"SCHILY.acl.access"));
+    assert!(archive.windows(18).any(|w| w == b"SCHILY.acl.default"));
+
+    at.remove("dir/file.txt");
+    at.rmdir("dir");
+
+    new_ucmd!()
+        .args(&["-xf", "archive.tar", "--acls"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .no_output();
+
+    assert_eq!(
+        xattr::get(at.plus("dir/file.txt"), "system.posix_acl_access").unwrap(),
+        Some(sample_acl())
+    );
+    assert_eq!(
+        xattr::get(at.plus("dir"), "system.posix_acl_default").unwrap(),
+        Some(sample_acl())
+    );
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_acls_not_restored_without_flag() {
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    at.write("file.txt", "content");
+    if xattr::set(
+        at.plus("file.txt"),
+        "system.posix_acl_access",
+        &sample_acl(),
+    )
+    .is_err()
+    {
+        eprintln!("skipping: filesystem does not support ACLs");
+        return;
+    }
 
-    // Extract with verbose
-    let result = new_ucmd!()
-        .arg("-xvf")
-        .arg(at.plus("archive.tar"))
-        .current_dir(at.as_string())
+    ucmd.args(&["-cf", "archive.tar", "--acls", "file.txt"])
         .succeeds();
+    at.remove("file.txt");
 
-    let stdout = result.stdout_str();
-
-    // Verify all files are listed in verbose output
-    assert!(stdout.contains("file1.txt"));
-    assert!(stdout.contains("file2.txt"));
-    assert!(stdout.contains("file3.txt"));
+    new_ucmd!()
+        .args(&["-xf", "archive.tar"])
+        .current_dir(at.as_string())
+        .succeeds();
 
-    // Verify files were extracted
-    assert!(at.file_exists("file1.txt"));
-    assert!(at.file_exists("file2.txt"));
-    assert!(at.file_exists("file3.txt"));
+    assert_eq!(
+        xattr::get(at.plus("file.txt"), "system.posix_acl_access").unwrap(),
+        None
+    );
 }
 
 #[test]
-fn test_extract_multiple_files() {
+#[cfg(target_os = "linux")]
+fn test_acls_not_stored_without_flag() {
     let (at, mut ucmd) = at_and_ucmd!();
 
-    // Create an archive with multiple files
-    at.write("file1.txt", "content1");
-    at.write("file2.txt", "content2");
-    ucmd.args(&["-cf", "archive.tar", "file1.txt", "file2.txt"])
+    at.write("file.txt", "content");
+    if xattr::set(
+        at.plus("file.txt"),
+        "system.posix_acl_access",
+        &sample_acl(),
+    )
+    .is_err()
+    {
+        eprintln!("skipping: filesystem does not support ACLs");
+        return;
+    }
+
+    // --xattrs alone leaves ACLs to --acls.
+    ucmd.args(&["-cf", "archive.tar", "--xattrs", "file.txt"])
         .succeeds();
+    let archive = at.read_bytes("archive.tar");
+    assert!(!archive.windows(10).any(|w| w == b"SCHILY.acl"));
+    assert!(!archive.windows(16).any(|w| w == b"system.posix_acl"));
+}
 
-    // Remove originals
-    at.remove("file1.txt");
-    at.remove("file2.txt");
+// SELinux tests
 
-    // Extract
-    new_ucmd!()
-        .arg("-xf")
-        .arg(at.plus("archive.tar"))
-        .current_dir(at.as_string())
-        .succeeds();
+#[cfg(target_os = "linux")]
+const SAMPLE_CONTEXT: &[u8] = b"system_u:object_r:user_home_t:s0";
 
-    assert!(at.file_exists("file1.txt"));
-    assert!(at.file_exists("file2.txt"));
-    assert_eq!(at.read("file1.txt"), "content1");
-    assert_eq!(at.read("file2.txt"), "content2");
+/// Builds an archive holding `file.txt` labelled with `SAMPLE_CONTEXT`.
+#[cfg(target_os = "linux")]
+fn selinux_archive() -> Vec<u8> {
+    let mut builder = tar_rs_crate::Builder::new(Vec::new());
+    builder
+        .append_pax_extensions([("RHT.security.selinux", SAMPLE_CONTEXT)])
+        .unwrap();
+    let mut header = tar_rs_crate::Header::new_gnu();
+    header.set_size(7);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder
+        .append_data(&mut header, "file.txt", &b"content"[..])
+        .unwrap();
+    builder.into_inner().unwrap()
 }
 
 #[test]
-fn test_extract_nonexistent_archive() {
-    new_ucmd!()
-        .args(&["-xf", "nonexistent.tar"])
-        .fails()
-        .code_is(2)
-        .stderr_contains("nonexistent.tar");
+#[cfg(target_os = "linux")]
+fn test_selinux_context_restored_or_warned() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    at.write_bytes("archive.tar", &selinux_archive());
+
+    let result = ucmd.args(&["-xf", "archive.tar", "--selinux"]).succeeds();
+    assert_eq!(at.read("file.txt"), "content");
+
+    // Without SELinux (or without privileges) the label cannot be applied,
+    // which is only worth a warning.
+    let mut expected = SAMPLE_CONTEXT.to_vec();
+    expected.push(0);
+    if xattr::get(at.plus("file.txt"), "security.selinux").unwrap() != Some(expected) {
+        result.stderr_contains("file.txt: Cannot set SELinux context");
+    }
 }
 
 #[test]
-fn test_extract_directory_structure() {
+#[cfg(target_os = "linux")]
+fn test_selinux_context_ignored_without_flag() {
     let (at, mut ucmd) = at_and_ucmd!();
+    at.write_bytes("archive.tar", &selinux_archive());
 
-    // Create directory structure
-    at.mkdir("testdir");
-    at.write("testdir/file1.txt", "content1");
-    at.mkdir("testdir/subdir");
-    at.write("testdir/subdir/file2.txt", "content2");
+    ucmd.args(&["-xf", "archive.tar"]).succeeds().no_output();
 
-    // Create archive
-    ucmd.args(&["-cf", "archive.tar", "testdir"]).succeeds();
+    let label = xattr::get(at.plus("file.txt"), "security.selinux").unwrap_or_default();
+    assert!(!label.is_some_and(|label| label.starts_with(SAMPLE_CONTEXT)));
+}
 
-    // Remove directory contents and directory itself
-    at.remove("testdir/subdir/file2.txt");
-    at.remove("testdir/file1.txt");
-    at.rmdir("testdir/subdir");
-    at.rmdir("testdir");
+#[test]
+#[cfg(target_os = "linux")]
+fn test_selinux_context_stored() {
+    let (at, mut ucmd) = at_and_ucmd!();
 
-    // Extract (extracts to current directory)
-    new_ucmd!()
-        .arg("-xf")
-        .arg(at.plus("archive.tar"))
-        .current_dir(at.as_string())
+    at.write("file.txt", "content");
+    let mut label = SAMPLE_CONTEXT.to_vec();
+    label.push(0);
+    if xattr::set(at.plus("file.txt"), "security.selinux", &label).is_err() {
+        eprintln!("skipping: cannot label files");
+        return;
+    }
+
+    ucmd.args(&["-cf", "archive.tar", "--selinux", "file.txt"])
         .succeeds();
 
-    // Verify structure
-    assert!(at.dir_exists("testdir"));
-    assert!(at.file_exists("testdir/file1.txt"));
-    assert!(at.dir_exists("testdir/subdir"));
-    assert!(at.file_exists("testdir/subdir/file2.txt"));
-    assert_eq!(at.read("testdir/file1.txt"), "content1");
-    assert_eq!(at.read("testdir/subdir/file2.txt"), "content2");
+    let archive = at.read_bytes("archive.tar");
+    let record = b"RHT.security.selinux=system_u:object_r:user_home_t:s0\n";
+    assert!(archive.windows(record.len()).any(|w| w == record));
 }
 
 // Round-trip tests
@@ -414,115 +2339,514 @@ fn test_create_permission_denied() {
 }
 
 #[test]
-fn test_extract_corrupted_archive() {
+fn test_extract_corrupted_archive() {
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    // Create a corrupted tar file (invalid header)
+    at.write("corrupted.tar", "This is not a valid tar file content");
+
+    ucmd.args(&["-xf", "corrupted.tar"]).fails().code_is(2);
+}
+
+#[test]
+fn test_create_with_dash_in_filename() {
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    // Create files starting with dash
+    at.write("-dash-file.txt", "content with dash");
+    at.write("normal.txt", "normal content");
+
+    ucmd.args(&["-cf", "archive.tar", "--", "-dash-file.txt", "normal.txt"])
+        .succeeds();
+
+    assert!(at.file_exists("archive.tar"));
+
+    // Verify extraction works
+    at.remove("-dash-file.txt");
+    at.remove("normal.txt");
+
+    new_ucmd!()
+        .arg("-xf")
+        .arg(at.plus("archive.tar"))
+        .current_dir(at.as_string())
+        .succeeds();
+
+    assert!(at.file_exists("-dash-file.txt"));
+    assert_eq!(at.read("-dash-file.txt"), "content with dash");
+}
+
+// CLI argument handling tests
+
+#[test]
+fn test_mixed_short_and_long_options() {
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    at.write("file.txt", "content");
+
+    // Test mixing -x with --file
+    ucmd.args(&["-c", "--file=archive.tar", "file.txt"])
+        .succeeds();
+
+    assert!(at.file_exists("archive.tar"));
+
+    at.remove("file.txt");
+
+    // Test extraction with mixed options
+    new_ucmd!()
+        .args(&["-x", "--file", "archive.tar"])
+        .current_dir(at.as_string())
+        .succeeds();
+
+    assert!(at.file_exists("file.txt"));
+}
+
+#[test]
+fn test_option_order_variations() {
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    at.write("file.txt", "content");
+
+    // Test standard -cf order
+    ucmd.args(&["-cf", "archive1.tar", "file.txt"]).succeeds();
+
+    assert!(at.file_exists("archive1.tar"));
+
+    // Test separate options
+    new_ucmd!()
+        .args(&["-c", "-f", "archive2.tar", "file.txt"])
+        .current_dir(at.as_string())
+        .succeeds();
+
+    assert!(at.file_exists("archive2.tar"));
+
+    // Test long form
+    new_ucmd!()
+        .args(&["--create", "--file", "archive3.tar", "file.txt"])
+        .current_dir(at.as_string())
+        .succeeds();
+
+    assert!(at.file_exists("archive3.tar"));
+}
+
+#[test]
+fn test_extract_overwrites_existing_by_default() {
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    // Create original file and archive
+    at.write("file.txt", "original content");
+    ucmd.args(&["-cf", "archive.tar", "file.txt"]).succeeds();
+
+    // Modify the file
+    at.write("file.txt", "modified content");
+
+    // Extract should overwrite
+    new_ucmd!()
+        .arg("-xf")
+        .arg(at.plus("archive.tar"))
+        .current_dir(at.as_string())
+        .succeeds();
+
+    // Verify original content is restored
+    assert_eq!(at.read("file.txt"), "original content");
+}
+
+#[test]
+fn test_extract_keep_old_files() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    at.write("file.txt", "original content");
+    at.write("new.txt", "new content");
+    ucmd.args(&["-cf", "archive.tar", "file.txt", "new.txt"])
+        .succeeds();
+    at.write("file.txt", "modified content");
+    at.remove("new.txt");
+
+    new_ucmd!()
+        .args(&["-xkf", "archive.tar"])
+        .current_dir(at.as_string())
+        .fails()
+        .code_is(2)
+        .stderr_is("tar: file.txt: Cannot open: File exists\n");
+    assert_eq!(at.read("file.txt"), "modified content");
+    assert_eq!(at.read("new.txt"), "new content");
+
+    new_ucmd!()
+        .args(&["-xf", "archive.tar", "--skip-old-files"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .no_output();
+    assert_eq!(at.read("file.txt"), "modified content");
+}
+
+#[test]
+fn test_extract_keep_newer_files() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    at.write("newer.txt", "archived");
+    at.write("older.txt", "archived");
+    set_mtime(&at, "newer.txt", 1_000_000_000);
+    set_mtime(&at, "older.txt", 1_000_000_000);
+    ucmd.args(&["-cf", "archive.tar", "newer.txt", "older.txt"])
+        .succeeds();
+
+    at.write("newer.txt", "local");
+    at.write("older.txt", "local");
+    set_mtime(&at, "older.txt", 900_000_000);
+
+    new_ucmd!()
+        .args(&["-xf", "archive.tar", "--keep-newer-files"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .stderr_is("tar: Current 'newer.txt' is newer or same age\n");
+    assert_eq!(at.read("newer.txt"), "local");
+    assert_eq!(at.read("older.txt"), "archived");
+}
+
+#[test]
+fn test_extract_over_directory() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    at.write("item", "file content");
+    ucmd.args(&["-cf", "archive.tar", "item"]).succeeds();
+    at.remove("item");
+    at.mkdir_all("item/sub");
+
+    // A directory in the way is only removed when empty, or with
+    // --recursive-unlink.
+    new_ucmd!()
+        .args(&["-xf", "archive.tar"])
+        .current_dir(at.as_string())
+        .fails()
+        .code_is(2)
+        .stderr_is("tar: item: Cannot open: File exists\n");
+    new_ucmd!()
+        .args(&["-xUf", "archive.tar"])
+        .current_dir(at.as_string())
+        .fails()
+        .code_is(2)
+        .stderr_contains("tar: item: Cannot unlink: ");
+    assert!(at.dir_exists("item/sub"));
+
+    new_ucmd!()
+        .args(&["-xf", "archive.tar", "--recursive-unlink"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .no_output();
+    assert_eq!(at.read("item"), "file content");
+}
+
+#[test]
+#[cfg(unix)]
+fn test_extract_keep_directory_symlink() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    at.mkdir("dir");
+    at.write("dir/file.txt", "content");
+    ucmd.args(&["-cf", "archive.tar", "dir"]).succeeds();
+    at.remove("dir/file.txt");
+    at.rmdir("dir");
+    at.mkdir("elsewhere");
+    at.symlink_dir("elsewhere", "dir");
+
+    new_ucmd!()
+        .args(&["-xf", "archive.tar", "--keep-directory-symlink"])
+        .current_dir(at.as_string())
+        .succeeds();
+    assert!(at.is_symlink("dir"));
+    assert_eq!(at.read("elsewhere/file.txt"), "content");
+
+    // By default the link is replaced with a directory.
+    at.remove("elsewhere/file.txt");
+    new_ucmd!()
+        .args(&["-xf", "archive.tar"])
+        .current_dir(at.as_string())
+        .succeeds();
+    assert!(!at.is_symlink("dir"));
+    assert_eq!(at.read("dir/file.txt"), "content");
+    assert!(!at.file_exists("elsewhere/file.txt"));
+}
+
+#[test]
+fn test_extract_overwrite_options_conflict() {
     let (at, mut ucmd) = at_and_ucmd!();
+    at.write("file.txt", "content");
+    ucmd.args(&["-cf", "archive.tar", "file.txt"]).succeeds();
 
-    // Create a corrupted tar file (invalid header)
-    at.write("corrupted.tar", "This is not a valid tar file content");
-
-    ucmd.args(&["-xf", "corrupted.tar"]).fails().code_is(2);
+    new_ucmd!()
+        .args(&["-xf", "archive.tar", "-k", "--overwrite"])
+        .current_dir(at.as_string())
+        .fails()
+        .stderr_contains("cannot be used with");
 }
 
 #[test]
-fn test_create_with_dash_in_filename() {
+fn test_extract_backup() {
     let (at, mut ucmd) = at_and_ucmd!();
+    at.write("file.txt", "archived");
+    ucmd.args(&["-cf", "archive.tar", "file.txt"]).succeeds();
 
-    // Create files starting with dash
-    at.write("-dash-file.txt", "content with dash");
-    at.write("normal.txt", "normal content");
-
-    ucmd.args(&["-cf", "archive.tar", "--", "-dash-file.txt", "normal.txt"])
-        .succeeds();
+    at.write("file.txt", "first");
+    new_ucmd!()
+        .args(&["-xvf", "archive.tar", "--backup=simple"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .stdout_contains("Renaming 'file.txt' to 'file.txt~'\n");
+    assert_eq!(at.read("file.txt"), "archived");
+    assert_eq!(at.read("file.txt~"), "first");
+
+    for _ in 0..2 {
+        new_ucmd!()
+            .args(&["-xf", "archive.tar", "--backup=numbered"])
+            .current_dir(at.as_string())
+            .succeeds()
+            .no_output();
+    }
+    assert_eq!(at.read("file.txt.~1~"), "archived");
+    assert_eq!(at.read("file.txt.~2~"), "archived");
+    assert_eq!(at.read("file.txt~"), "first");
 
-    assert!(at.file_exists("archive.tar"));
+    // Backups are taken before the overwrite options are looked at.
+    at.write("file.txt", "second");
+    new_ucmd!()
+        .args(&["-xkf", "archive.tar", "--backup=simple", "--suffix=.bak"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .no_output();
+    assert_eq!(at.read("file.txt"), "archived");
+    assert_eq!(at.read("file.txt.bak"), "second");
+}
 
-    // Verify extraction works
-    at.remove("-dash-file.txt");
-    at.remove("normal.txt");
+#[test]
+fn test_extract_backup_skips_directories() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    at.mkdir("dir");
+    at.write("dir/file.txt", "archived");
+    ucmd.args(&["-cf", "archive.tar", "dir"]).succeeds();
 
     new_ucmd!()
-        .arg("-xf")
-        .arg(at.plus("archive.tar"))
+        .args(&["-xf", "archive.tar", "--backup=simple"])
         .current_dir(at.as_string())
         .succeeds();
-
-    assert!(at.file_exists("-dash-file.txt"));
-    assert_eq!(at.read("-dash-file.txt"), "content with dash");
+    assert!(!at.file_exists("dir~"));
+    assert_eq!(at.read("dir/file.txt~"), "archived");
 }
 
-// CLI argument handling tests
-
 #[test]
-fn test_mixed_short_and_long_options() {
+fn test_extract_backup_invalid_control() {
     let (at, mut ucmd) = at_and_ucmd!();
-
     at.write("file.txt", "content");
+    ucmd.args(&["-cf", "archive.tar", "file.txt"]).succeeds();
 
-    // Test mixing -x with --file
-    ucmd.args(&["-c", "--file=archive.tar", "file.txt"])
-        .succeeds();
+    new_ucmd!()
+        .args(&["-xf", "archive.tar", "--backup=bogus"])
+        .current_dir(at.as_string())
+        .fails()
+        .code_is(2)
+        .stderr_contains("invalid argument 'bogus'");
+    assert_eq!(at.read("file.txt"), "content");
+    assert!(!at.file_exists("file.txt~"));
+}
 
-    assert!(at.file_exists("archive.tar"));
+/// Runs tar in `dir` with `args`, answering its `-w` questions with
+/// `answers`, and returns its exit code, standard output and standard
+/// error. The child starts a session of its own and so has no controlling
+/// terminal, which sends the questions to standard error and reads the
+/// answers from standard input however the tests themselves are run.
+#[cfg(unix)]
+fn run_without_terminal(dir: &str, args: &[&str], answers: &str) -> (i32, String, String) {
+    use std::io::Write;
+    use std::os::unix::process::CommandExt;
+    use std::process::{Command, Stdio};
+
+    let mut command = Command::new(crate::TESTS_BINARY);
+    command
+        .args(args)
+        .current_dir(dir)
+        .env("TZ", "UTC")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    // SAFETY: setsid is async-signal-safe, so calling it between fork and
+    // exec is sound.
+    unsafe {
+        command.pre_exec(|| {
+            rustix::process::setsid()
+                .map(drop)
+                .map_err(std::io::Error::from)
+        });
+    }
+    let mut child = command.spawn().unwrap();
+    // tar may stop reading once it has every answer it needs.
+    let _ = child.stdin.take().unwrap().write_all(answers.as_bytes());
+    let output = child.wait_with_output().unwrap();
+    (
+        output.status.code().unwrap(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
 
-    at.remove("file.txt");
+#[test]
+#[cfg(unix)]
+fn test_create_interactive() {
+    let (at, _) = at_and_ucmd!();
+    at.write("a.txt", "a");
+    at.write("b.txt", "b");
+    at.mkdir("dir");
+    at.write("dir/c.txt", "c");
+
+    // Declining a directory leaves out its contents too.
+    let result = run_without_terminal(
+        &at.as_string(),
+        &["cwf", "archive.tar", "a.txt", "dir", "b.txt"],
+        "y\nn\nyes\n",
+    );
+    assert_eq!(
+        result,
+        (
+            0,
+            String::new(),
+            "add 'a.txt'?add 'dir'?add 'b.txt'?".into()
+        )
+    );
 
-    // Test extraction with mixed options
     new_ucmd!()
-        .args(&["-x", "--file", "archive.tar"])
+        .args(&["-tf", "archive.tar"])
         .current_dir(at.as_string())
-        .succeeds();
+        .succeeds()
+        .stdout_only("a.txt\nb.txt\n");
+}
 
-    assert!(at.file_exists("file.txt"));
+#[test]
+#[cfg(unix)]
+fn test_extract_interactive() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    at.write("a.txt", "a");
+    at.write("b.txt", "b");
+    at.write("c.txt", "c");
+    ucmd.args(&["-cf", "archive.tar", "a.txt", "b.txt", "c.txt"])
+        .succeeds();
+    at.remove("a.txt");
+    at.remove("b.txt");
+    at.remove("c.txt");
+
+    // Running out of answers declines the rest.
+    let result = run_without_terminal(
+        &at.as_string(),
+        &["-xf", "archive.tar", "--interactive"],
+        "n\nY\n",
+    );
+    assert_eq!(
+        result,
+        (
+            0,
+            String::new(),
+            "extract 'a.txt'?extract 'b.txt'?extract 'c.txt'?\n".into()
+        )
+    );
+    assert!(!at.file_exists("a.txt"));
+    assert_eq!(at.read("b.txt"), "b");
+    assert!(!at.file_exists("c.txt"));
 }
 
 #[test]
-fn test_option_order_variations() {
+#[cfg(unix)]
+fn test_list_interactive() {
     let (at, mut ucmd) = at_and_ucmd!();
+    at.write("a.txt", "a");
+    at.write("b.txt", "b");
+    ucmd.args(&["-cf", "archive.tar", "a.txt", "b.txt"])
+        .succeeds();
 
-    at.write("file.txt", "content");
+    let result = run_without_terminal(
+        &at.as_string(),
+        &["-tf", "archive.tar", "--confirmation"],
+        "y\nn\n",
+    );
+    assert_eq!(
+        result,
+        (0, "a.txt\n".into(), "list 'a.txt'?list 'b.txt'?".into())
+    );
 
-    // Test standard -cf order
-    ucmd.args(&["-cf", "archive1.tar", "file.txt"]).succeeds();
+    // The questions stay out of the index file.
+    let result = run_without_terminal(
+        &at.as_string(),
+        &["-tvwf", "archive.tar", "--index-file=index.txt"],
+        "n\ny\n",
+    );
+    assert_eq!(
+        result,
+        (0, String::new(), "list 'a.txt'?list 'b.txt'?".into())
+    );
+    assert!(at.read("index.txt").ends_with(" b.txt\n"));
+    assert!(!at.read("index.txt").contains("list"));
+}
 
-    assert!(at.file_exists("archive1.tar"));
+#[test]
+fn test_extract_to_stdout() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    at.mkdir("dir");
+    at.write("dir/a.txt", "alpha\n");
+    at.write("b.txt", "beta\n");
+    ucmd.args(&["-cf", "archive.tar", "dir", "b.txt"])
+        .succeeds();
+    at.remove("dir/a.txt");
+    at.rmdir("dir");
+    at.remove("b.txt");
 
-    // Test separate options
+    // The listing moves to stderr so that it does not mix with the data.
     new_ucmd!()
-        .args(&["-c", "-f", "archive2.tar", "file.txt"])
+        .args(&["-xvOf", "archive.tar"])
         .current_dir(at.as_string())
-        .succeeds();
+        .succeeds()
+        .stdout_is("alpha\nbeta\n")
+        .stderr_contains("dir/a.txt\nb.txt\n");
+    assert!(!at.dir_exists("dir"));
+    assert!(!at.file_exists("b.txt"));
+}
 
-    assert!(at.file_exists("archive2.tar"));
+#[cfg(unix)]
+#[test]
+fn test_extract_to_command() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    at.mkdir("dir");
+    at.write("dir/a.txt", "alpha\n");
+    ucmd.args(&["-cf", "archive.tar", "dir"]).succeeds();
+    at.remove("dir/a.txt");
+    at.rmdir("dir");
 
-    // Test long form
     new_ucmd!()
-        .args(&["--create", "--file", "archive3.tar", "file.txt"])
+        .args(&[
+            "-xf",
+            "archive.tar",
+            "--to-command=echo \"$TAR_FILENAME $TAR_FILETYPE $TAR_SIZE $TAR_MODE\"; cat",
+        ])
         .current_dir(at.as_string())
-        .succeeds();
-
-    assert!(at.file_exists("archive3.tar"));
+        .succeeds()
+        .stdout_only("dir/a.txt f 6 0644\nalpha\n");
+    assert!(!at.dir_exists("dir"));
 }
 
+#[cfg(unix)]
 #[test]
-fn test_extract_overwrites_existing_by_default() {
+fn test_extract_to_command_failure() {
     let (at, mut ucmd) = at_and_ucmd!();
-
-    // Create original file and archive
-    at.write("file.txt", "original content");
+    at.write("file.txt", "content");
     ucmd.args(&["-cf", "archive.tar", "file.txt"]).succeeds();
 
-    // Modify the file
-    at.write("file.txt", "modified content");
-
-    // Extract should overwrite
     new_ucmd!()
-        .arg("-xf")
-        .arg(at.plus("archive.tar"))
+        .args(&["-xf", "archive.tar", "--to-command=exit 3"])
         .current_dir(at.as_string())
-        .succeeds();
+        .fails()
+        .code_is(2)
+        .stderr_contains("Child returned status 3");
 
-    // Verify original content is restored
-    assert_eq!(at.read("file.txt"), "original content");
+    new_ucmd!()
+        .args(&[
+            "-xf",
+            "archive.tar",
+            "--to-command=exit 3",
+            "--ignore-command-error",
+        ])
+        .current_dir(at.as_string())
+        .succeeds()
+        .no_output();
 }
 
 // Edge case tests
@@ -700,24 +3024,41 @@ fn test_posix_and_dash_prefix_both_work() {
 }
 
 #[test]
-fn test_posix_b_matches_dash_prefix_failure() {
+fn test_posix_b_matches_dash_prefix() {
     let (at, mut ucmd) = at_and_ucmd!();
 
     at.write("file.txt", "hello");
 
-    // Dash-prefixed form currently rejects '-b'.
     new_ucmd!()
-        .args(&["-cbf", "20", "dash.tar", "file.txt"])
+        .args(&["-cb", "4", "-f", "dash.tar", "file.txt"])
         .current_dir(at.as_string())
-        .fails()
-        .code_is(64)
-        .stderr_contains("unexpected argument '-b'");
+        .succeeds();
 
-    // POSIX keystring form should fail with the same unsupported option.
-    ucmd.args(&["cbf", "20", "posix.tar", "file.txt"])
-        .fails()
-        .code_is(64)
-        .stderr_contains("unexpected argument '-b'");
+    // In the keystring, 'b' takes its operand before 'f' does.
+    ucmd.args(&["cbf", "4", "posix.tar", "file.txt"]).succeeds();
+
+    assert_eq!(at.metadata("dash.tar").len(), 4 * TAR_BLOCK_SIZE as u64);
+    assert_eq!(
+        at.read_bytes("posix.tar").len(),
+        at.read_bytes("dash.tar").len()
+    );
+}
+
+#[test]
+fn test_posix_m_keystring() {
+    let (at, mut ucmd) = at_and_ucmd!();
+
+    at.write("file.txt", "hello");
+    set_mtime(&at, "file.txt", 1_000_000_000);
+    ucmd.args(&["cf", "archive.tar", "file.txt"]).succeeds();
+    at.remove("file.txt");
+
+    new_ucmd!()
+        .args(&["xmf", "archive.tar"])
+        .current_dir(at.as_string())
+        .succeeds();
+
+    assert!(mtime_of(&at, "file.txt") > UNIX_EPOCH + Duration::from_secs(1_000_000_000));
 }
 
 // List operation tests
@@ -788,6 +3129,270 @@ fn test_list_verbose() {
         .stdout_contains("7 "); // verbose output includes file size; absent from plain -t listing
 }
 
+#[test]
+fn test_list_verbose_matches_gnu_layout() {
+    let mut builder = tar_rs_crate::Builder::new(Vec::new());
+    let mut append = |entry_type, name: &str, mode, owner: &str, link: Option<&str>| {
+        let mut header = tar_rs_crate::Header::new_gnu();
+        header.set_entry_type(entry_type);
+        header.set_mode(mode);
+        header.set_mtime(86_400);
+        header.set_username(owner).unwrap();
+        header.set_groupname("staff").unwrap();
+        if let Some(link) = link {
+            header.set_link_name(link).unwrap();
+        }
+        if entry_type == tar_rs_crate::EntryType::Char {
+            header.set_device_major(4).unwrap();
+            header.set_device_minor(64).unwrap();
+        }
+        let data: &[u8] = if entry_type.is_file() {
+            b"content"
+        } else {
+            b""
+        };
+        header.set_size(data.len() as u64);
+        builder.append_data(&mut header, name, data).unwrap();
+    };
+    append(
+        tar_rs_crate::EntryType::Regular,
+        "file.txt",
+        0o4755,
+        "root",
+        None,
+    );
+    append(
+        tar_rs_crate::EntryType::Link,
+        "hard",
+        0o644,
+        "root",
+        Some("file.txt"),
+    );
+    append(
+        tar_rs_crate::EntryType::Symlink,
+        "soft",
+        0o777,
+        "root",
+        Some("file.txt"),
+    );
+    append(tar_rs_crate::EntryType::Char, "tty", 0o620, "root", None);
+    // A wider entry widens the column for good.
+    append(
+        tar_rs_crate::EntryType::Regular,
+        "long",
+        0o644,
+        "averyveryverylongname",
+        None,
+    );
+    append(tar_rs_crate::EntryType::Fifo, "fifo", 0o644, "root", None);
+    append(
+        tar_rs_crate::EntryType::new(b'X'),
+        "odd",
+        0o644,
+        "root",
+        None,
+    );
+
+    let (at, mut ucmd) = at_and_ucmd!();
+    at.write_bytes("archive.tar", &builder.into_inner().unwrap());
+
+    ucmd.args(&["-tvf", "archive.tar"])
+        .env("TZ", "UTC")
+        .succeeds()
+        .stdout_only(concat!(
+            "-rwsr-xr-x root/staff        7 1970-01-02 00:00 file.txt\n",
+            "hrw-r--r-- root/staff        0 1970-01-02 00:00 hard link to file.txt\n",
+            "lrwxrwxrwx root/staff        0 1970-01-02 00:00 soft -> file.txt\n",
+            "crw--w---- root/staff     4,64 1970-01-02 00:00 tty\n",
+            "-rw-r--r-- averyveryverylongname/staff 7 1970-01-02 00:00 long\n",
+            "prw-r--r-- root/staff                  0 1970-01-02 00:00 fifo\n",
+            "?rw-r--r-- root/staff                  0 1970-01-02 00:00 odd\n",
+        ));
+}
+
+#[test]
+fn test_list_verbose_uses_local_time() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    at.write("file.txt", "content");
+    set_mtime(&at, "file.txt", 0);
+    ucmd.args(&["-cf", "archive.tar", "file.txt"]).succeeds();
+
+    new_ucmd!()
+        .args(&["-tvf", "archive.tar"])
+        .env("TZ", "JST-9")
+        .current_dir(at.as_string())
+        .succeeds()
+        .stdout_contains(" 1970-01-01 09:00 file.txt\n");
+}
+
+#[test]
+fn test_list_full_time_utc_numeric_owner() {
+    let mut builder = tar_rs_crate::Builder::new(Vec::new());
+    builder
+        .append_pax_extensions([("mtime", &b"1700000000.5"[..])])
+        .unwrap();
+    let mut header = tar_rs_crate::Header::new_ustar();
+    header.set_size(7);
+    header.set_mode(0o644);
+    header.set_uid(1000);
+    header.set_gid(100);
+    header.set_username("alice").unwrap();
+    header.set_groupname("users").unwrap();
+    header.set_mtime(1_700_000_000);
+    header.set_cksum();
+    builder
+        .append_data(&mut header, "file.txt", &b"content"[..])
+        .unwrap();
+
+    let (at, mut ucmd) = at_and_ucmd!();
+    at.write_bytes("archive.tar", &builder.into_inner().unwrap());
+
+    ucmd.args(&["-tvf", "archive.tar", "--full-time", "--utc"])
+        .env("TZ", "JST-9")
+        .succeeds()
+        .stdout_only("-rw-r--r-- alice/users       7 2023-11-14 22:13:20.5 file.txt\n");
+
+    new_ucmd!()
+        .args(&["-tvf", "archive.tar", "--numeric-owner"])
+        .env("TZ", "JST-9")
+        .current_dir(at.as_string())
+        .succeeds()
+        .stdout_only("-rw-r--r-- 1000/100          7 2023-11-15 07:13 file.txt\n");
+}
+
+#[test]
+fn test_double_verbose_lists_in_full() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    at.write("file.txt", "content");
+    set_mtime(&at, "file.txt", 86_400);
+
+    ucmd.args(&["-cvvf", "archive.tar", "file.txt"])
+        .env("TZ", "UTC")
+        .succeeds()
+        .stdout_contains(" 7 1970-01-02 00:00 file.txt\n");
+
+    at.remove("file.txt");
+    new_ucmd!()
+        .args(&["-xvvf", "archive.tar"])
+        .env("TZ", "UTC")
+        .current_dir(at.as_string())
+        .succeeds()
+        .stdout_contains(" 7 1970-01-02 00:00 file.txt\n");
+    assert!(at.file_exists("file.txt"));
+}
+
+#[test]
+fn test_block_number() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    at.write("first.txt", &"x".repeat(600));
+    at.write("second.txt", "content");
+
+    ucmd.args(&["-cvRf", "archive.tar", "first.txt", "second.txt"])
+        .succeeds()
+        .stdout_only("block 0: first.txt\nblock 3: second.txt\n");
+
+    new_ucmd!()
+        .args(&["-tRf", "archive.tar"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .stdout_only("block 0: first.txt\nblock 3: second.txt\nblock 5: ** Block of NULs **\n");
+
+    new_ucmd!()
+        .args(&["-tRif", "archive.tar"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .stdout_only("block 0: first.txt\nblock 3: second.txt\nblock 20: ** End of File **\n");
+}
+
+#[test]
+fn test_index_file() {
+    let (at, mut ucmd) = at_and_ucmd!();
+    at.write("file.txt", "content");
+
+    ucmd.args(&["-cvf", "archive.tar", "--index-file=create.idx", "file.txt"])
+        .succeeds()
+        .no_stdout();
+    assert_eq!(at.read("create.idx"), "file.txt\n");
+
+    new_ucmd!()
+        .args(&["-tRf", "archive.tar", "--index-file", "list.idx"])
+        .current_dir(at.as_string())
+        .succeeds()
+        .no_stdout();
+    assert_eq!(
+        at.read("list.idx"),
+        "block 0: file.txt\nblock 2: ** Block of NULs **\n"
+    );
+
+    new_ucmd!()
+        .args(&["-tf", "archive.tar", "--index-file=missing/list.idx"])
+        .current_dir(at.as_string())
+        .fails()
+        .code_is(2)
+        .stderr_contains("missing/list.idx: Cannot open: No such file or directory");
+}
+
+#[test]
+fn test_list_verbose_applies_pax_records() {
+    let mut builder = tar_rs_crate::Builder::new(Vec::new());
+    let mut global = tar_rs_crate::Header::new_ustar();
+    global.set_entry_type(tar_rs_crate::EntryType::XGlobalHeader);
+    global.set_size(15);
+    global.set_cksum();
+    builder.append(&global, &b"15 gname=staff\n"[..]).unwrap();
+    builder
+        .append_pax_extensions([("uname", &b"alice"[..]), ("size", &b"7"[..])])
+        .unwrap();
+    let mut header = tar_rs_crate::Header::new_ustar();
+    header.set_size(7);
+    header.set_mode(0o644);
+    header.set_uid(0);
+    header.set_gid(0);
+    header.set_username("root").unwrap();
+    header.set_cksum();
+    builder
+        .append_data(&mut header, "file.txt", &b"content"[..])
+        .unwrap();
+
+    let (at, mut ucmd) = at_and_ucmd!();
+    at.write_bytes("archive.tar", &builder.into_inner().unwrap());
+
+    // The global header is not a member of its own.
+    ucmd.args(&["-tvf", "archive.tar"])
+        .succeeds()
+        .stdout_contains("alice/staff")
+        .stdout_does_not_contain("GlobalHead")
+        .stdout_does_not_contain("root");
+}
+
+#[test]
+fn test_extract_restores_pax_mtime_fraction() {
+    let mut builder = tar_rs_crate::Builder::new(Vec::new());
+    builder
+        .append_pax_extensions([("mtime", &b"1700000000.5"[..])])
+        .unwrap();
+    let mut header = tar_rs_crate::Header::new_ustar();
+    header.set_size(7);
+    header.set_mode(0o644);
+    header.set_mtime(1_700_000_000);
+    header.set_cksum();
+    builder
+        .append_data(&mut header, "file.txt", &b"content"[..])
+        .unwrap();
+
+    let (at, mut ucmd) = at_and_ucmd!();
+    at.write_bytes("archive.tar", &builder.into_inner().unwrap());
+    ucmd.args(&["-xf", "archive.tar"]).succeeds();
+
+    let mtime = at
+        .metadata("file.txt")
+        .modified()
+        .unwrap()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap();
+    assert_eq!(mtime.as_millis(), 1_700_000_000_500);
+}
+
 #[test]
 fn test_list_nonexistent_archive() {
     new_ucmd!()